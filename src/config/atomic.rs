@@ -0,0 +1,48 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it into
+/// place, so a crash or full disk halfway through never leaves a truncated
+/// config behind.
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = temp_path(path);
+    let result = fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    #[test]
+    fn replaces_existing_file_without_leaving_temp_file() {
+        let dir = TempDir::new("atomic");
+        let path = dir.join("options.ini");
+        write_atomic(&path, "[peacock]\njokes=false\n").unwrap();
+        write_atomic(&path, "[peacock]\njokes=true\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[peacock]\njokes=true\n"
+        );
+        assert!(!temp_path(&path).exists());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::config::peacock::{PeacockCategory, get_installed_peacock_version, save_peacock_config};
+use crate::config::spear::{SpearConfig, is_valid_repo, load_spear_config, save_spear_config};
+use crate::constants::SPEAR_PATH;
+use crate::error::{Context, SpearError, SpearResult};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+const REPO_CHANGE_NAME: &str = "spear.peacock_github_repo";
+
+/// The parts of [`SpearConfig`] that are safe to hand to someone else. Fields
+/// are listed explicitly so anything sensitive added later stays out by default.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundledSpearConfig {
+    pub peacock_github_repo: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SettingsBundle {
+    pub format_version: u32,
+    #[serde(default)]
+    pub peacock_version: Option<String>,
+    pub spear: BundledSpearConfig,
+    /// Section name -> option key -> value, as in `options.ini`.
+    #[serde(default)]
+    pub peacock: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BundleChange {
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportPreview {
+    pub changes: Vec<BundleChange>,
+    pub warnings: Vec<String>,
+}
+
+pub fn get_exports_dir() -> PathBuf {
+    SPEAR_PATH.join("exports")
+}
+
+pub fn create_bundle(
+    spear_config: &SpearConfig,
+    categories: &[PeacockCategory],
+    peacock_version: Option<String>,
+) -> SettingsBundle {
+    let mut peacock = BTreeMap::new();
+    for cat in categories {
+        let section: &mut BTreeMap<String, String> = peacock.entry(cat.name.clone()).or_default();
+        for opt in &cat.options {
+            section.insert(opt.key.clone(), opt.value.clone());
+        }
+    }
+    SettingsBundle {
+        format_version: BUNDLE_FORMAT_VERSION,
+        peacock_version,
+        spear: BundledSpearConfig {
+            peacock_github_repo: spear_config.peacock_github_repo.clone(),
+        },
+        peacock,
+    }
+}
+
+pub fn bundle_to_string(bundle: &SettingsBundle) -> SpearResult<String> {
+    toml::to_string(bundle).context("Failed to serialize settings bundle")
+}
+
+pub fn bundle_from_str(content: &str) -> SpearResult<SettingsBundle> {
+    let bundle: SettingsBundle =
+        toml::from_str(content).context("Failed to parse settings bundle")?;
+    if bundle.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(SpearError::format(
+            "Failed to read settings bundle",
+            format!(
+                "format {} is newer than supported format {}",
+                bundle.format_version, BUNDLE_FORMAT_VERSION
+            ),
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Writes the current settings to a timestamped bundle under `SPEAR_PATH/exports`.
+pub fn export_bundle(
+    spear_config: &SpearConfig,
+    categories: &[PeacockCategory],
+) -> SpearResult<PathBuf> {
+    let dir = get_exports_dir();
+    fs::create_dir_all(&dir).context("Failed to create the exports folder")?;
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let path = dir.join(format!("spear-settings-{}.toml", timestamp));
+    let bundle = create_bundle(spear_config, categories, get_installed_peacock_version());
+    let content = bundle_to_string(&bundle)?;
+    fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+    log::info!("[+] Exported settings bundle to {:?}", path);
+    Ok(path)
+}
+
+pub fn read_bundle(path: &Path) -> SpearResult<SettingsBundle> {
+    log::info!("[+] Reading settings bundle from {:?}", path);
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    bundle_from_str(&content)
+}
+
+/// Validates `bundle` against the current flag definitions and lists what
+/// applying it would change.
+pub fn preview_import(
+    bundle: &SettingsBundle,
+    spear_config: &SpearConfig,
+    categories: &[PeacockCategory],
+) -> ImportPreview {
+    let mut preview = ImportPreview::default();
+
+    if bundle.spear.peacock_github_repo != spear_config.peacock_github_repo {
+        preview.changes.push(BundleChange {
+            name: REPO_CHANGE_NAME.to_string(),
+            old: spear_config.peacock_github_repo.clone(),
+            new: bundle.spear.peacock_github_repo.clone(),
+        });
+    }
+    if !is_valid_repo(&bundle.spear.peacock_github_repo) {
+        preview.warnings.push(format!(
+            "Peacock GitHub '{}' is not in owner/name form",
+            bundle.spear.peacock_github_repo
+        ));
+    }
+    if let Some(theirs) = &bundle.peacock_version {
+        if let Some(ours) = get_installed_peacock_version().filter(|ours| ours != theirs) {
+            preview.warnings.push(format!(
+                "Bundle was exported from Peacock {}, installed is {}",
+                theirs, ours
+            ));
+        }
+    }
+
+    for (section, values) in &bundle.peacock {
+        let Some(cat) = categories.iter().find(|c| c.name == *section) else {
+            preview
+                .warnings
+                .push(format!("Unknown section [{}] will be ignored", section));
+            continue;
+        };
+        for (key, value) in values {
+            let Some(opt) = cat.options.iter().find(|o| o.key == *key) else {
+                preview.warnings.push(format!(
+                    "Unknown option {}.{} will be ignored",
+                    section, key
+                ));
+                continue;
+            };
+            if let Some(possible_values) = &opt.possible_values {
+                if !possible_values.contains(value) {
+                    preview.warnings.push(format!(
+                        "{}.{} = '{}' is not one of {} and will be ignored",
+                        section,
+                        key,
+                        value,
+                        possible_values.join(", ")
+                    ));
+                    continue;
+                }
+            } else if (opt.value == "true" || opt.value == "false")
+                && value != "true"
+                && value != "false"
+            {
+                preview.warnings.push(format!(
+                    "{}.{} = '{}' is not a boolean and will be ignored",
+                    section, key, value
+                ));
+                continue;
+            }
+            if opt.value != *value {
+                preview.changes.push(BundleChange {
+                    name: format!("{}.{}", section, key),
+                    old: opt.value.clone(),
+                    new: value.clone(),
+                });
+            }
+        }
+    }
+
+    preview
+}
+
+/// Applies the changes listed by [`preview_import`] and clears the active
+/// profile so the imported options are what Peacock starts with.
+pub fn apply_import(
+    preview: &ImportPreview,
+    categories: &mut [PeacockCategory],
+) -> SpearResult<()> {
+    let mut config = load_spear_config();
+    for change in &preview.changes {
+        if change.name == REPO_CHANGE_NAME {
+            config.peacock_github_repo = change.new.clone();
+        } else if let Some((section, key)) = change.name.split_once('.') {
+            if let Some(opt) = categories
+                .iter_mut()
+                .find(|c| c.name == section)
+                .and_then(|c| c.options.iter_mut().find(|o| o.key == key))
+            {
+                opt.value = change.new.clone();
+            }
+        }
+    }
+    config.active_profile = None;
+    *crate::config::profiles::ACTIVE_PROFILE.lock().unwrap() = None;
+    save_spear_config(&config)?;
+    save_peacock_config(categories)?;
+    log::info!("[+] Imported {} setting(s)", preview.changes.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_support::{choice, option, section};
+
+    fn categories() -> Vec<PeacockCategory> {
+        vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                choice(
+                    "mapDiscoveryState",
+                    "KEEP",
+                    &["REVEALED", "CLOUDED", "KEEP"],
+                ),
+                option("leaderboardsHost", "https://backend.rdil.rocks"),
+            ],
+        )]
+    }
+
+    fn bundle(values: &[(&str, &str)]) -> SettingsBundle {
+        let mut section = BTreeMap::new();
+        for (key, value) in values {
+            section.insert(key.to_string(), value.to_string());
+        }
+        let mut peacock = BTreeMap::new();
+        peacock.insert("peacock".to_string(), section);
+        SettingsBundle {
+            format_version: BUNDLE_FORMAT_VERSION,
+            peacock_version: None,
+            spear: BundledSpearConfig {
+                peacock_github_repo: SpearConfig::default().peacock_github_repo,
+            },
+            peacock,
+        }
+    }
+
+    #[test]
+    fn bundle_round_trips_through_toml() {
+        let bundle = bundle(&[("jokes", "true"), ("mapDiscoveryState", "REVEALED")]);
+        let text = bundle_to_string(&bundle).unwrap();
+        assert_eq!(bundle_from_str(&text).unwrap(), bundle);
+    }
+
+    #[test]
+    fn newer_bundle_format_is_rejected() {
+        let mut bundle = bundle(&[]);
+        bundle.format_version = BUNDLE_FORMAT_VERSION + 1;
+        let text = bundle_to_string(&bundle).unwrap();
+        assert!(bundle_from_str(&text).is_err());
+    }
+
+    #[test]
+    fn preview_lists_only_changed_values() {
+        let preview = preview_import(
+            &bundle(&[("jokes", "true"), ("mapDiscoveryState", "KEEP")]),
+            &SpearConfig::default(),
+            &categories(),
+        );
+        assert!(preview.warnings.is_empty());
+        assert_eq!(
+            preview.changes,
+            vec![BundleChange {
+                name: "peacock.jokes".to_string(),
+                old: "false".to_string(),
+                new: "true".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn preview_rejects_invalid_values_and_unknown_keys() {
+        let preview = preview_import(
+            &bundle(&[
+                ("jokes", "maybe"),
+                ("mapDiscoveryState", "FOGGY"),
+                ("removedFlag", "1"),
+            ]),
+            &SpearConfig::default(),
+            &categories(),
+        );
+        assert!(preview.changes.is_empty());
+        assert_eq!(preview.warnings.len(), 3);
+    }
+
+    #[test]
+    fn exported_spear_config_only_contains_allowed_fields() {
+        let config = SpearConfig {
+            active_profile: Some("speedrun".to_string()),
+            ..SpearConfig::default()
+        };
+        let text = bundle_to_string(&create_bundle(&config, &categories(), None)).unwrap();
+        assert!(!text.contains("active_profile"));
+        assert!(text.contains("peacock_github_repo"));
+    }
+}
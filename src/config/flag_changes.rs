@@ -0,0 +1,338 @@
+use crate::config::peacock::{
+    IniEntry, PeacockCategory, get_flags_cache_path, get_installed_peacock_repo,
+    get_installed_peacock_version, get_peacock_config_path, parse_flags_object, parse_ini,
+    parse_peacock_defaults, read_installed_flags_object, store_flags_cache,
+};
+use crate::constants::SPEAR_PATH;
+use crate::error::Context;
+
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// One flag that was added, removed or changed between two Peacock versions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlagChange {
+    pub section: String,
+    pub key: String,
+    pub description: String,
+    pub old_default: Option<String>,
+    pub new_default: Option<String>,
+    pub old_possible_values: Option<Vec<String>>,
+    pub new_possible_values: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlagChanges {
+    pub from_version: String,
+    pub to_version: String,
+    pub added: Vec<FlagChange>,
+    pub removed: Vec<FlagChange>,
+    pub changed: Vec<FlagChange>,
+    pub archived_path: Option<PathBuf>,
+}
+
+impl FlagChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+lazy_static! {
+    /// The "what's new in settings" summary for the last detected upgrade,
+    /// until the user dismisses it.
+    pub static ref FLAG_CHANGES: Mutex<Option<FlagChanges>> = Mutex::new(None);
+}
+
+fn get_last_seen_version_path() -> PathBuf {
+    SPEAR_PATH
+        .join("cache")
+        .join("flags")
+        .join("last_version.txt")
+}
+
+/// The repo the last seen version came from, to find its cached definitions.
+fn get_last_seen_repo_path() -> PathBuf {
+    SPEAR_PATH.join("cache").join("flags").join("last_repo.txt")
+}
+
+pub fn compare_flag_definitions(old: &[PeacockCategory], new: &[PeacockCategory]) -> FlagChanges {
+    let mut changes = FlagChanges::default();
+    for new_cat in new {
+        let old_cat = old.iter().find(|c| c.name == new_cat.name);
+        for new_opt in &new_cat.options {
+            let old_opt = old_cat.and_then(|c| c.options.iter().find(|o| o.key == new_opt.key));
+            let change = FlagChange {
+                section: new_cat.name.clone(),
+                key: new_opt.key.clone(),
+                description: new_opt.description.clone(),
+                old_default: old_opt.map(|o| o.value.clone()),
+                new_default: Some(new_opt.value.clone()),
+                old_possible_values: old_opt.and_then(|o| o.possible_values.clone()),
+                new_possible_values: new_opt.possible_values.clone(),
+            };
+            match old_opt {
+                None => changes.added.push(change),
+                Some(old_opt) => {
+                    if old_opt.value != new_opt.value
+                        || old_opt.possible_values != new_opt.possible_values
+                    {
+                        changes.changed.push(change);
+                    }
+                }
+            }
+        }
+    }
+    for old_cat in old {
+        let new_cat = new.iter().find(|c| c.name == old_cat.name);
+        for old_opt in &old_cat.options {
+            if new_cat.is_some_and(|c| c.options.iter().any(|o| o.key == old_opt.key)) {
+                continue;
+            }
+            changes.removed.push(FlagChange {
+                section: old_cat.name.clone(),
+                key: old_opt.key.clone(),
+                description: old_opt.description.clone(),
+                old_default: Some(old_opt.value.clone()),
+                new_default: None,
+                old_possible_values: old_opt.possible_values.clone(),
+                new_possible_values: None,
+            });
+        }
+    }
+    changes
+}
+
+/// Returns the entries of a known section whose key no longer has a definition.
+/// Sections we know nothing about (e.g. from plugins) are left alone.
+pub fn find_orphaned_values(
+    entries: &[IniEntry],
+    definitions: &[PeacockCategory],
+) -> Vec<IniEntry> {
+    entries
+        .iter()
+        .filter(|e| {
+            definitions
+                .iter()
+                .find(|c| c.name == e.section)
+                .is_some_and(|c| !c.options.iter().any(|o| o.key == e.key))
+        })
+        .cloned()
+        .collect()
+}
+
+fn archive_orphaned_values(definitions: &[PeacockCategory], from_version: &str) -> Option<PathBuf> {
+    let content = fs::read_to_string(get_peacock_config_path()).ok()?;
+    let orphaned = find_orphaned_values(&parse_ini(&content), definitions);
+    if orphaned.is_empty() {
+        return None;
+    }
+
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let path = SPEAR_PATH
+        .join("backups")
+        .join(format!("options-removed-{}.ini", timestamp));
+    let mut backup = format!("; Values of flags removed after Peacock {}\n", from_version);
+    let mut current_section: Option<&str> = None;
+    for entry in &orphaned {
+        if current_section != Some(entry.section.as_str()) {
+            backup.push_str(&format!("[{}]\n", entry.section));
+            current_section = Some(entry.section.as_str());
+        }
+        backup.push_str(&format!("{}={}\n", entry.key, entry.value));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    match fs::write(&path, backup) {
+        Ok(_) => {
+            log::info!(
+                "[+] Archived {} orphaned option(s) to {:?}",
+                orphaned.len(),
+                path
+            );
+            Some(path)
+        }
+        Err(e) => {
+            log::error!(
+                "[!] Failed to archive orphaned options to {:?}: {}",
+                path,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Caches the flag definitions of the currently installed release so they
+/// can still be compared against once it has been replaced.
+pub fn cache_installed_flag_definitions() {
+    let (Some(repo), Some(tag)) = (
+        get_installed_peacock_repo(),
+        get_installed_peacock_version(),
+    ) else {
+        return;
+    };
+    if get_flags_cache_path(&repo, &tag).exists() {
+        return;
+    }
+    match read_installed_flags_object() {
+        Ok(obj_str) => store_flags_cache(&repo, &tag, &obj_str),
+        Err(e) => log::error!("[!] Failed to cache installed flag definitions: {}", e),
+    }
+}
+
+/// Compares the flag definitions of the previously seen Peacock version with
+/// the installed one and publishes the result in [`FLAG_CHANGES`].
+pub fn detect_flag_changes() {
+    let Some(installed) = get_installed_peacock_version() else {
+        return;
+    };
+    let last_seen_path = get_last_seen_version_path();
+    let last_seen = fs::read_to_string(&last_seen_path)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+
+    if last_seen.as_deref() != Some(installed.as_str()) {
+        let new_definitions = parse_peacock_defaults();
+        if let Some(previous) = &last_seen {
+            log::info!(
+                "[+] Peacock version changed from {} to {}",
+                previous,
+                installed
+            );
+            let repo = fs::read_to_string(get_last_seen_repo_path())
+                .ok()
+                .map(|r| r.trim().to_string())
+                .filter(|r| !r.is_empty())
+                .or_else(get_installed_peacock_repo)
+                .unwrap_or_else(crate::config::runtime::get_peacock_repo);
+            let old_definitions = fs::read_to_string(get_flags_cache_path(&repo, previous))
+                .context(format!("Failed to read cached flags of {}", previous))
+                .and_then(|obj_str| parse_flags_object(&obj_str));
+            match old_definitions {
+                Ok(old_definitions) => {
+                    let mut changes = compare_flag_definitions(&old_definitions, &new_definitions);
+                    changes.from_version = previous.clone();
+                    changes.to_version = installed.clone();
+                    changes.archived_path = archive_orphaned_values(&new_definitions, previous);
+                    log::info!(
+                        "[+] Flag changes: {} added, {} removed, {} changed",
+                        changes.added.len(),
+                        changes.removed.len(),
+                        changes.changed.len()
+                    );
+                    if !changes.is_empty() || changes.archived_path.is_some() {
+                        *FLAG_CHANGES.lock().unwrap() = Some(changes);
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "[!] No flag definitions cached for {}, skipping comparison: {}",
+                        previous,
+                        e
+                    );
+                    archive_orphaned_values(&new_definitions, previous);
+                }
+            }
+        }
+
+        if let Some(parent) = last_seen_path.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        fs::write(&last_seen_path, &installed).ok();
+        if let Some(repo) = get_installed_peacock_repo() {
+            fs::write(get_last_seen_repo_path(), repo).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_support::{choice, option, section};
+
+    #[test]
+    fn compare_detects_added_removed_and_changed_flags() {
+        let old = vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                option("legacyThing", "true"),
+                choice("imageLoading", "ONLINE", &["ONLINE", "OFFLINE"]),
+            ],
+        )];
+        let new = vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                option("newThing", "true"),
+                choice(
+                    "imageLoading",
+                    "ONLINE",
+                    &["SAVEASREQUESTED", "ONLINE", "OFFLINE"],
+                ),
+            ],
+        )];
+        let changes = compare_flag_definitions(&old, &new);
+        assert_eq!(
+            changes
+                .added
+                .iter()
+                .map(|c| c.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["newThing"]
+        );
+        assert_eq!(changes.added[0].description, "newThing description");
+        assert_eq!(
+            changes
+                .removed
+                .iter()
+                .map(|c| c.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["legacyThing"]
+        );
+        assert_eq!(changes.removed[0].new_default, None);
+        assert_eq!(
+            changes
+                .changed
+                .iter()
+                .map(|c| c.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["imageLoading"]
+        );
+    }
+
+    #[test]
+    fn compare_detects_changed_default() {
+        let old = vec![section("peacock", vec![option("leaderboards", "true")])];
+        let new = vec![section("peacock", vec![option("leaderboards", "false")])];
+        let changes = compare_flag_definitions(&old, &new);
+        assert!(changes.added.is_empty() && changes.removed.is_empty());
+        assert_eq!(changes.changed[0].old_default.as_deref(), Some("true"));
+        assert_eq!(changes.changed[0].new_default.as_deref(), Some("false"));
+    }
+
+    #[test]
+    fn identical_definitions_have_no_changes() {
+        let defs = vec![section("peacock", vec![option("jokes", "false")])];
+        assert!(compare_flag_definitions(&defs, &defs).is_empty());
+    }
+
+    #[test]
+    fn orphaned_values_only_come_from_known_sections() {
+        let defs = vec![section("peacock", vec![option("jokes", "false")])];
+        let entries = parse_ini("[peacock]\njokes=true\nremovedFlag=1\n\n[myPlugin]\nsetting=2\n");
+        assert_eq!(
+            find_orphaned_values(&entries, &defs),
+            vec![IniEntry {
+                section: "peacock".to_string(),
+                key: "removedFlag".to_string(),
+                value: "1".to_string(),
+            }]
+        );
+    }
+}
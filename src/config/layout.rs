@@ -0,0 +1,224 @@
+use crate::core::logs::Severity;
+use crate::error::{Context, SpearError, SpearResult};
+use crate::gui::layout::{DEFAULT_LAYOUT, DESIGN_SIZE, Layout, LayoutSpec};
+use crate::gui::notifications::notify;
+
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Fonts outside this range are a typo, not a style.
+const FONT_HEIGHT_RANGE: std::ops::RangeInclusive<i32> = 1..=200;
+const FONT_WEIGHT_RANGE: std::ops::RangeInclusive<i32> = 1..=1000;
+
+lazy_static! {
+    static ref LAYOUT_SPEC: Mutex<LayoutSpec> = Mutex::new(LayoutSpec::default());
+    /// Set whenever the spec changes so the overlay lays itself out again.
+    pub static ref LAYOUT_CHANGED: Mutex<bool> = Mutex::new(false);
+}
+
+pub fn get_layout_path() -> PathBuf {
+    crate::core::paths::SPEAR_PATHS
+        .config_dir
+        .join("layout.toml")
+}
+
+pub fn layout_spec() -> LayoutSpec {
+    LAYOUT_SPEC.lock().unwrap().clone()
+}
+
+/// Applies `spec` to the overlay without saving it.
+pub fn set_layout_spec(spec: LayoutSpec) {
+    *LAYOUT_SPEC.lock().unwrap() = spec;
+    *LAYOUT_CHANGED.lock().unwrap() = true;
+}
+
+/// Copies every value in `user` over `base`, descending into tables so a
+/// user file can change a single key of a section.
+fn merge_tables(base: &mut toml::Table, user: toml::Table) {
+    for (key, value) in user {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(user)) => merge_tables(base, user),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Keeps the values in `spec` that differ from `base`, descending into tables
+/// so a section only lists the keys that changed.
+fn diff_tables(base: &toml::Table, spec: toml::Table) -> toml::Table {
+    spec.into_iter()
+        .filter_map(|(key, value)| match (base.get(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(spec)) => {
+                let diff = diff_tables(base, spec);
+                (!diff.is_empty()).then_some((key, toml::Value::Table(diff)))
+            }
+            (Some(base), value) if *base == value => None,
+            (_, value) => Some((key, value)),
+        })
+        .collect()
+}
+
+/// Parses a user `layout.toml` on top of the built-in one.
+pub fn parse_layout(content: &str) -> SpearResult<LayoutSpec> {
+    let mut table: toml::Table =
+        toml::from_str(DEFAULT_LAYOUT).context("Failed to read the built-in layout")?;
+    let user: toml::Table = toml::from_str(content).context("Failed to parse layout.toml")?;
+    merge_tables(&mut table, user);
+    toml::Value::Table(table)
+        .try_into()
+        .context("Failed to parse layout.toml")
+}
+
+/// Checks a layout at the launcher's design size, returning one message per
+/// problem.
+pub fn validate_layout(spec: &LayoutSpec) -> Vec<String> {
+    let mut errors = vec![];
+    let window = egui::Rect::from_min_size(egui::Pos2::ZERO, DESIGN_SIZE);
+    for (name, rect) in Layout::compute(spec, DESIGN_SIZE, 1.0).rects() {
+        if rect.width() <= 0.0 || rect.height() <= 0.0 {
+            errors.push(format!("{} must have a positive width and height", name));
+        } else if !window.contains_rect(rect) {
+            errors.push(format!(
+                "{} ({}, {} to {}, {}) is outside the {}x{} launcher window",
+                name, rect.min.x, rect.min.y, rect.max.x, rect.max.y, DESIGN_SIZE.x, DESIGN_SIZE.y
+            ));
+        }
+    }
+    for (name, font) in [
+        ("play_text_font", spec.play_text_font),
+        ("version_font", spec.version_font),
+    ] {
+        if !FONT_HEIGHT_RANGE.contains(&font.height) {
+            errors.push(format!(
+                "{}.height must be between {} and {}",
+                name,
+                FONT_HEIGHT_RANGE.start(),
+                FONT_HEIGHT_RANGE.end()
+            ));
+        }
+        if !FONT_WEIGHT_RANGE.contains(&font.weight) {
+            errors.push(format!(
+                "{}.weight must be between {} and {}",
+                name,
+                FONT_WEIGHT_RANGE.start(),
+                FONT_WEIGHT_RANGE.end()
+            ));
+        }
+    }
+    errors
+}
+
+fn read_layout() -> SpearResult<Option<LayoutSpec>> {
+    let path = get_layout_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).context("Failed to read layout.toml")?;
+    let spec = parse_layout(&content)?;
+    let errors = validate_layout(&spec);
+    if !errors.is_empty() {
+        return Err(SpearError::format("Invalid layout.toml", errors.join("; ")));
+    }
+    Ok(Some(spec))
+}
+
+/// Loads the user's `layout.toml`, falling back to the built-in layout when
+/// there is none or it is invalid.
+pub fn reload_layout() {
+    let spec = match read_layout() {
+        Ok(Some(spec)) => {
+            log::info!("[+] Loaded layout from {:?}", get_layout_path());
+            spec
+        }
+        Ok(None) => LayoutSpec::default(),
+        Err(e) => {
+            log::error!("[!] {}", e);
+            notify(
+                Severity::Warn,
+                "layout.toml is invalid, using the built-in layout",
+                Some(e.to_string()),
+            );
+            LayoutSpec::default()
+        }
+    };
+    set_layout_spec(spec);
+}
+
+/// `layout.toml` content for `spec`, holding only what differs from the
+/// built-in layout so later changes to the defaults still reach the user.
+fn layout_to_string(spec: &LayoutSpec) -> SpearResult<String> {
+    let base = toml::Table::try_from(LayoutSpec::default())
+        .context("Failed to serialize the built-in layout")?;
+    let spec = toml::Table::try_from(spec).context("Failed to serialize layout.toml")?;
+    toml::to_string(&diff_tables(&base, spec)).context("Failed to serialize layout.toml")
+}
+
+pub fn save_layout(spec: &LayoutSpec) -> SpearResult<()> {
+    let errors = validate_layout(spec);
+    if !errors.is_empty() {
+        return Err(SpearError::format("Invalid layout", errors.join("; ")));
+    }
+    let content = layout_to_string(spec)?;
+    crate::config::atomic::write_atomic(&get_layout_path(), &content)
+        .context("Failed to save layout.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_layout_is_valid() {
+        assert_eq!(
+            validate_layout(&LayoutSpec::default()),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn user_file_overrides_single_values() {
+        let spec =
+            parse_layout("[play_button]\nx = 80.0\n\n[version_font]\nheight = 24\n").unwrap();
+        let default = LayoutSpec::default();
+        assert_eq!(spec.play_button.x, 80.0);
+        assert_eq!(spec.play_button.y, default.play_button.y);
+        assert_eq!(spec.version_font.height, 24);
+        assert_eq!(spec.profile, default.profile);
+        assert_eq!(parse_layout("").unwrap(), default);
+    }
+
+    #[test]
+    fn saved_layout_reads_back() {
+        let mut spec = LayoutSpec::default();
+        spec.progress_bar.width = 150.5;
+        let content = layout_to_string(&spec).unwrap();
+        assert_eq!(content, "[progress_bar]\nwidth = 150.5\n");
+        assert_eq!(parse_layout(&content).unwrap(), spec);
+        assert_eq!(layout_to_string(&LayoutSpec::default()).unwrap(), "");
+    }
+
+    #[test]
+    fn typos_are_rejected() {
+        assert!(parse_layout("[play_buton]\nx = 80.0\n").is_err());
+        assert!(parse_layout("[play_button]\nanchor = \"middle\"\n").is_err());
+    }
+
+    #[test]
+    fn off_window_rects_are_rejected() {
+        let spec = parse_layout("[play_button]\nx = 500.0\n").unwrap();
+        let errors = validate_layout(&spec);
+        // The text moves with the button and leaves the window too.
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("play_button"));
+
+        let spec = parse_layout("[version]\nx = 10.0\n[play_text_font]\nheight = 0\n").unwrap();
+        let errors = validate_layout(&spec);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[1].starts_with("play_text_font.height"));
+        assert!(save_layout(&spec).is_err());
+    }
+}
@@ -1,2 +1,11 @@
+pub mod atomic;
+pub mod bundle;
+pub mod flag_changes;
+pub mod layout;
 pub mod peacock;
+pub mod profiles;
+pub mod runtime;
 pub mod spear;
+#[cfg(test)]
+pub mod test_support;
+pub mod watch;
@@ -1,24 +1,54 @@
+use crate::constants::SPEAR_PATH;
+use crate::error::{Context, SpearError, SpearResult};
+
+use lazy_static::lazy_static;
 use octocrab::Octocrab;
-use serde::{Deserialize};
+use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PeacockOption {
     pub key: String,
     pub value: String,
+    pub category: String,
     pub description: String,
     pub possible_values: Option<Vec<String>>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PeacockCategory {
     pub name: String,
     pub options: Vec<PeacockOption>,
 }
 
+/// Where the flag definitions currently in use were loaded from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlagsSource {
+    Cache,
+    Installed,
+    Network,
+    Embedded,
+}
+
+impl FlagsSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FlagsSource::Cache => "cache",
+            FlagsSource::Installed => "installed chunk0.js",
+            FlagsSource::Network => "GitHub",
+            FlagsSource::Embedded => "built-in fallback",
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref FLAGS_SOURCE: Mutex<Option<FlagsSource>> = Mutex::new(None);
+}
+
 #[derive(Deserialize)]
 struct FlagDef {
     category: String,
@@ -42,39 +72,68 @@ struct DefaultFlags {
 }
 
 pub fn get_peacock_config_path() -> PathBuf {
-    let mut path = dirs::data_local_dir().unwrap();
-    path.push("spear");
-    path.push("peacock");
-    path.push("options.ini");
-    path
+    SPEAR_PATH.join("peacock").join("options.ini")
 }
 
+/// Finds the `defaultFlags = { ... }` assignment in either `flags.ts` or the
+/// bundled `chunk0.js` and returns the object literal. Braces and quotes in
+/// strings and comments don't count towards the matching `}`.
 fn extract_object(text: &str) -> Option<String> {
-    let start = text.find("export const defaultFlags")?;
-    let after = &text[start..];
-    let eq_pos = after.find('=')?;
-    let after_eq = &after[eq_pos + 1..];
-    let brace_start = after_eq.find('{')?;
-    let mut brace_count = 0;
-    let mut end_pos = 0;
-    for (i, c) in after_eq[brace_start..].char_indices() {
-        match c {
-            '{' => brace_count += 1,
-            '}' => {
-                brace_count -= 1;
-                if brace_count == 0 {
-                    end_pos = brace_start + i + 1;
-                    break;
+    text.match_indices("defaultFlags")
+        .find_map(|(start, name)| {
+            let mut after = text[start + name.len()..].trim_start();
+            if let Some(rest) = after.strip_prefix(':') {
+                let rest = rest.trim_start();
+                let type_len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                after = rest[type_len..].trim_start();
+            }
+            let after_eq = after.strip_prefix('=')?.trim_start();
+            if !after_eq.starts_with('{') {
+                return None;
+            }
+            let mut brace_count = 0;
+            let mut in_string: Option<char> = None;
+            let mut escaped = false;
+            let mut skip_to = 0;
+            for (i, c) in after_eq.char_indices() {
+                if i < skip_to {
+                    continue;
+                }
+                if let Some(quote) = in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == quote {
+                        in_string = None;
+                    }
+                    continue;
+                }
+                let rest = &after_eq[i..];
+                if rest.starts_with("//") {
+                    skip_to = i + rest.find('\n').unwrap_or(rest.len());
+                    continue;
+                }
+                if let Some(comment) = rest.strip_prefix("/*") {
+                    skip_to = i + comment.find("*/").map_or(rest.len(), |end| end + 4);
+                    continue;
+                }
+                match c {
+                    '"' | '\'' | '`' => in_string = Some(c),
+                    '{' => brace_count += 1,
+                    '}' => {
+                        brace_count -= 1;
+                        if brace_count == 0 {
+                            return Some(after_eq[..i + 1].to_string());
+                        }
+                    }
+                    _ => {}
                 }
             }
-            _ => {}
-        }
-    }
-    if end_pos > 0 {
-        Some(after_eq[brace_start..end_pos].to_string())
-    } else {
-        None
-    }
+            None
+        })
 }
 
 const HARDCODED_FLAGS_TS: &str = r#"/*
@@ -428,25 +487,71 @@ export function getAllFlags(): IIniObject {
 
 pub fn parse_peacock_defaults() -> Vec<PeacockCategory> {
     log::info!("[+] Parsing Peacock defaults");
-    // Try to fetch from GitHub
-    match fetch_peacock_defaults() {
-        Ok(categories) => {
-            log::info!(
-                "[+] Fetched defaults from GitHub, categories: {}",
-                categories.len()
-            );
-            return categories;
+    let (categories, source) = resolve_peacock_defaults();
+    log::info!(
+        "[+] Using flag definitions from {}, options: {}",
+        source.label(),
+        categories.iter().map(|c| c.options.len()).sum::<usize>()
+    );
+    *FLAGS_SOURCE.lock().unwrap() = Some(source);
+    categories
+}
+
+/// Resolves the flag definitions in order: the per-version cache, the installed
+/// `chunk0.js` bundle, GitHub, and finally the embedded `flags.ts`. The cache
+/// is keyed by the repo the installed release came from, which an override of
+/// the runtime repo doesn't change.
+fn resolve_peacock_defaults() -> (Vec<PeacockCategory>, FlagsSource) {
+    let installed_repo = get_installed_peacock_repo();
+    let installed_tag = get_installed_peacock_version();
+
+    if let (Some(repo), Some(tag)) = (&installed_repo, &installed_tag) {
+        let cache_path = get_flags_cache_path(repo, tag);
+        if let Ok(obj_str) = fs::read_to_string(&cache_path) {
+            match parse_flags_object(&obj_str) {
+                Ok(categories) => return (categories, FlagsSource::Cache),
+                Err(e) => log::error!("[!] Cached flags at {:?} are invalid: {}", cache_path, e),
+            }
         }
-        Err(e) => {
-            log::error!("[+] Fetch failed with error: {}", e);
-            log::info!("[+] Using hardcoded flags.ts");
+
+        match read_installed_flags_object() {
+            Ok(obj_str) => match parse_flags_object(&obj_str) {
+                Ok(categories) => {
+                    store_flags_cache(repo, tag, &obj_str);
+                    return (categories, FlagsSource::Installed);
+                }
+                Err(e) => log::error!("[!] Failed to parse flags from chunk0.js: {}", e),
+            },
+            Err(e) => log::error!("[!] Failed to extract flags from chunk0.js: {}", e),
         }
     }
-    
-    let obj_str = extract_object(HARDCODED_FLAGS_TS)
-        .ok_or("Failed to extract object")
-        .unwrap();
-    let default_flags: DefaultFlags = json5::from_str(&obj_str).unwrap();
+
+    let repo = installed_repo.unwrap_or_else(crate::config::runtime::get_peacock_repo);
+    match fetch_flags_object(&repo, installed_tag.as_deref()) {
+        Ok((obj_str, tag)) => match parse_flags_object(&obj_str) {
+            Ok(categories) => {
+                store_flags_cache(&repo, &tag, &obj_str);
+                return (categories, FlagsSource::Network);
+            }
+            Err(e) => log::error!("[!] Failed to parse fetched flags.ts: {}", e),
+        },
+        Err(e) => log::error!("[+] Fetch failed with error: {}", e),
+    }
+
+    log::info!("[+] Using hardcoded flags.ts");
+    let categories = extract_object(HARDCODED_FLAGS_TS)
+        .context("defaultFlags not found in the embedded flags.ts")
+        .and_then(|obj_str| parse_flags_object(&obj_str))
+        .unwrap_or_else(|e| {
+            log::error!("[!] {}", e);
+            vec![]
+        });
+    (categories, FlagsSource::Embedded)
+}
+
+pub(crate) fn parse_flags_object(obj_str: &str) -> SpearResult<Vec<PeacockCategory>> {
+    let default_flags: DefaultFlags = json5::from_str(obj_str)
+        .map_err(|e| SpearError::format("Failed to parse flag definitions", e))?;
     let mut options = vec![];
     for (key, flag) in &default_flags.peacock.flags {
         let value = match &flag.default {
@@ -458,49 +563,99 @@ pub fn parse_peacock_defaults() -> Vec<PeacockCategory> {
         options.push(PeacockOption {
             key: key.clone(),
             value,
+            category: flag.category.clone(),
             description: flag.desc.clone(),
             possible_values: flag.possible_values.clone(),
         });
     }
-    log::info!("[+] Parsed hardcoded flags.ts, options: {}", options.len());
-    vec![PeacockCategory {
+    options.sort_by(|a, b| a.category.cmp(&b.category).then_with(|| a.key.cmp(&b.key)));
+    Ok(vec![PeacockCategory {
         name: "peacock".to_string(),
         options,
-    }]
+    }])
+}
+
+pub fn get_installed_peacock_version() -> Option<String> {
+    let path = SPEAR_PATH.join("peacock").join("version.txt");
+    fs::read_to_string(path)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// The `owner/name` repo the installed release was downloaded from. Installs
+/// made before this was recorded have none.
+pub fn get_installed_peacock_repo() -> Option<String> {
+    let path = SPEAR_PATH.join("peacock").join("repo.txt");
+    fs::read_to_string(path)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
 }
 
-fn fetch_peacock_defaults() -> Result<Vec<PeacockCategory>, Box<dyn std::error::Error>> {
+pub(crate) fn get_flags_cache_path(repo: &str, tag: &str) -> PathBuf {
+    let sanitize = |s: &str| -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    SPEAR_PATH
+        .join("cache")
+        .join("flags")
+        .join(sanitize(repo))
+        .join(format!("{}.json5", sanitize(tag)))
+}
+
+pub(crate) fn store_flags_cache(repo: &str, tag: &str, obj_str: &str) {
+    let path = get_flags_cache_path(repo, tag);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    match fs::write(&path, obj_str) {
+        Ok(_) => log::info!("[+] Cached flag definitions at {:?}", path),
+        Err(e) => log::error!("[!] Failed to cache flag definitions at {:?}: {}", path, e),
+    }
+}
+
+pub(crate) fn read_installed_flags_object() -> SpearResult<String> {
+    let path = SPEAR_PATH.join("peacock").join("chunk0.js");
+    log::info!("[+] Extracting flag definitions from {:?}", path);
+    let text = fs::read_to_string(&path).context("Failed to read chunk0.js")?;
+    extract_object(&text).context("defaultFlags not found in chunk0.js")
+}
+
+fn fetch_flags_object(repo: &str, tag: Option<&str>) -> SpearResult<(String, String)> {
     log::info!("[+] Fetching Peacock defaults from GitHub");
-    let config = crate::config::spear::load_spear_config();
-    let repo = &config.peacock_github_repo;
     log::info!("[+] Using repo: {}", repo);
 
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    let tag = rt
-        .block_on(async {
-            let octocrab = Octocrab::builder().build().map_err(|e| {
-                log::error!("[+] Failed to build Octocrab: {}", e);
-                e
-            })?;
-            let release = octocrab
-                .repos(
-                    repo.split('/').next().unwrap().to_string(),
-                    repo.split('/').nth(1).unwrap().to_string(),
-                )
-                .releases()
-                .get_latest()
-                .await
-                .map_err(|e| {
-                    log::error!("[+] Failed to get latest release from GitHub API: {}", e);
-                    e
-                })?;
-            Ok::<String, Box<dyn std::error::Error>>(release.tag_name)
-        })
-        .map_err(|e| {
-            log::error!("[+] Octocrab fetch failed: {}", e);
-            e
-        })?;
-    log::info!("[+] Latest tag: {}", tag);
+    let tag = match tag {
+        Some(tag) => tag.to_string(),
+        None => {
+            let (owner, name) = repo
+                .split_once('/')
+                .context(format!("Invalid repo {:?}, expected owner/name", repo))?;
+            let rt = tokio::runtime::Runtime::new().context("Failed to start the fetch runtime")?;
+            rt.block_on(async {
+                let octocrab = Octocrab::builder().build()?;
+                let release = octocrab
+                    .repos(owner.to_string(), name.to_string())
+                    .releases()
+                    .get_latest()
+                    .await?;
+                Ok::<String, octocrab::Error>(release.tag_name)
+            })
+            .map_err(|e| {
+                SpearError::network(format!("Failed to get the latest release of {}", repo), e)
+            })?
+        }
+    };
+    log::info!("[+] Using tag: {}", tag);
 
     let client = reqwest::blocking::Client::new();
     let url = format!(
@@ -511,88 +666,161 @@ fn fetch_peacock_defaults() -> Result<Vec<PeacockCategory>, Box<dyn std::error::
     let text = client
         .get(&url)
         .send()
-        .map_err(|e| {
-            log::error!("[+] Failed to send GET request to {}: {}", url, e);
-            e
-        })?
-        .text()
-        .map_err(|e| {
-            log::error!("[+] Failed to read text from response: {}", e);
-            e
-        })?;
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.text())
+        .map_err(|e| SpearError::network(format!("Failed to fetch {}", url), e))?;
     log::info!("[+] Downloaded flags.ts, length: {}", text.len());
-    let obj_str = extract_object(&text).ok_or_else(|| {
-        log::error!("[+] Failed to extract object from flags.ts");
-        "Failed to extract object"
-    })?;
+    let obj_str = extract_object(&text).context("defaultFlags not found in flags.ts")?;
     log::info!("[+] Extracted object, length: {}", obj_str.len());
-    let default_flags: DefaultFlags = json5::from_str(&obj_str).map_err(|e| {
-        log::error!("[+] Failed to parse extracted object as JSON5: {}", e);
-        e
-    })?;
-    log::info!(
-        "[+] Parsed flags, count: {}",
-        default_flags.peacock.flags.len()
-    );
-    let mut options = vec![];
-    for (key, flag) in &default_flags.peacock.flags {
-        let value = match &flag.default {
-            Value::Bool(b) => b.to_string(),
-            Value::String(s) => s.clone(),
-            Value::Number(n) => n.to_string(),
-            _ => "".to_string(),
-        };
-        options.push(PeacockOption {
-            key: key.clone(),
-            value,
-            description: flag.desc.clone(),
-            possible_values: flag.possible_values.clone(),
-        });
+    Ok((obj_str, tag))
+}
+
+/// A single `key=value` line from an INI file, with the section it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IniEntry {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+}
+
+/// Parses `options.ini` the same way Peacock does: comments start with `;`
+/// and entries outside of any section are ignored.
+pub fn parse_ini(content: &str) -> Vec<IniEntry> {
+    let mut entries = vec![];
+    let mut current_section: Option<String> = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            current_section = Some(line[1..line.len() - 1].to_string());
+        } else if !line.is_empty() && !line.starts_with(';') {
+            if let (Some(eq_idx), Some(sec)) = (line.find('='), &current_section) {
+                entries.push(IniEntry {
+                    section: sec.clone(),
+                    key: line[..eq_idx].trim().to_string(),
+                    value: line[eq_idx + 1..].trim().to_string(),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// Overwrites the values of known options with `entries`; unknown keys are ignored.
+pub fn apply_ini_entries(categories: &mut [PeacockCategory], entries: Vec<IniEntry>) {
+    for entry in entries {
+        if let Some(cat) = categories.iter_mut().find(|c| c.name == entry.section) {
+            if let Some(opt) = cat.options.iter_mut().find(|o| o.key == entry.key) {
+                opt.value = entry.value;
+            }
+        }
     }
-    log::info!("[+] Created options, count: {}", options.len());
-    Ok(vec![PeacockCategory {
-        name: "peacock".to_string(),
-        options,
-    }])
 }
 
 pub fn load_peacock_config() -> Vec<PeacockCategory> {
+    load_peacock_config_with_defaults(&parse_peacock_defaults())
+}
+
+/// Loads `options.ini` on top of already resolved defaults, so callers that
+/// also need the defaults don't resolve them twice.
+pub fn load_peacock_config_with_defaults(defaults: &[PeacockCategory]) -> Vec<PeacockCategory> {
     let path = get_peacock_config_path();
     log::info!("[+] Loading Peacock config from: {:?}", path);
-    let mut defaults = parse_peacock_defaults();
+    let mut defaults = defaults.to_vec();
     if path.exists() {
         log::info!("[+] Config file exists, loading values");
         let content = fs::read_to_string(&path).unwrap_or_default();
-        let mut current_section: Option<String> = None;
-        for line in content.lines() {
-            let line = line.trim();
-            if line.starts_with('[') && line.ends_with(']') {
-                current_section = Some(line[1..line.len() - 1].to_string());
-            } else if !line.is_empty() && !line.starts_with(';') {
-                if let Some(eq_idx) = line.find('=') {
-                    let key = line[..eq_idx].trim().to_string();
-                    let value = line[eq_idx + 1..].trim().to_string();
-                    if let Some(sec) = &current_section {
-                        if let Some(cat) = defaults.iter_mut().find(|c| &c.name == sec) {
-                            if let Some(opt) = cat.options.iter_mut().find(|o| o.key == key) {
-                                opt.value = value;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        apply_ini_entries(&mut defaults, parse_ini(&content));
     } else {
         log::info!("[+] Config file does not exist, saving defaults");
-        save_peacock_config(&defaults);
+        if let Err(e) = save_peacock_config(&defaults) {
+            log::error!("[!] Failed to save default Peacock config: {}", e);
+        }
     }
     log::info!("[+] Loaded Peacock config, categories: {}", defaults.len());
     defaults
 }
 
-pub fn save_peacock_config(categories: &Vec<PeacockCategory>) {
-    let path = get_peacock_config_path();
-    fs::create_dir_all(path.parent().unwrap()).unwrap();
+/// An option whose current value differs from its Peacock default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionDiff {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    pub default: String,
+}
+
+pub fn get_default_value<'a>(
+    defaults: &'a [PeacockCategory],
+    section: &str,
+    key: &str,
+) -> Option<&'a str> {
+    defaults
+        .iter()
+        .find(|c| c.name == section)?
+        .options
+        .iter()
+        .find(|o| o.key == key)
+        .map(|o| o.value.as_str())
+}
+
+/// Lists every option in `current` whose value differs from `defaults`.
+/// Options without a known default are skipped.
+pub fn diff_against_defaults(
+    current: &[PeacockCategory],
+    defaults: &[PeacockCategory],
+) -> Vec<OptionDiff> {
+    let mut diffs = vec![];
+    for cat in current {
+        for opt in &cat.options {
+            if let Some(default) = get_default_value(defaults, &cat.name, &opt.key) {
+                if opt.value != default {
+                    diffs.push(OptionDiff {
+                        section: cat.name.clone(),
+                        key: opt.key.clone(),
+                        value: opt.value.clone(),
+                        default: default.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    diffs
+}
+
+/// Checks edited values against the flag definitions before they are written.
+pub fn validate_peacock_options(
+    categories: &[PeacockCategory],
+    defaults: &[PeacockCategory],
+) -> Vec<String> {
+    let mut errors = vec![];
+    for cat in categories {
+        for opt in &cat.options {
+            let name = format!("{}.{}", cat.name, opt.key);
+            if opt.value.contains(['\n', '\r']) {
+                errors.push(format!("{} must be a single line", name));
+            } else if let Some(possible_values) = &opt.possible_values {
+                if !possible_values.contains(&opt.value) {
+                    errors.push(format!(
+                        "{} = '{}' is not one of {}",
+                        name,
+                        opt.value,
+                        possible_values.join(", ")
+                    ));
+                }
+            } else if matches!(
+                get_default_value(defaults, &cat.name, &opt.key),
+                Some("true" | "false")
+            ) && opt.value != "true"
+                && opt.value != "false"
+            {
+                errors.push(format!("{} = '{}' is not a boolean", name, opt.value));
+            }
+        }
+    }
+    errors
+}
+
+pub fn save_peacock_config(categories: &[PeacockCategory]) -> SpearResult<()> {
     let mut content = String::new();
     for cat in categories {
         content.push_str(&format!("[{}]\n", cat.name));
@@ -601,5 +829,159 @@ pub fn save_peacock_config(categories: &Vec<PeacockCategory>) {
         }
         content.push('\n');
     }
-    fs::write(&path, content).unwrap();
+    crate::config::atomic::write_atomic(&get_peacock_config_path(), &content)
+        .context("Failed to save options.ini")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_support::{choice, option, section};
+
+    fn flag_keys(obj_str: &str) -> Vec<String> {
+        parse_flags_object(obj_str).unwrap()[0]
+            .options
+            .iter()
+            .map(|o| o.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn object_is_extracted_from_flags_ts() {
+        let obj_str = extract_object(HARDCODED_FLAGS_TS).unwrap();
+        assert!(obj_str.starts_with('{') && obj_str.ends_with('}'));
+        assert!(!flag_keys(&obj_str).is_empty());
+    }
+
+    #[test]
+    fn object_is_extracted_from_bundled_chunk0() {
+        let chunk0 = r#"var import_fs = require("fs");
+loadFlags(defaultFlags);
+var defaultFlags = {
+  peacock: {
+    title: "Peacock",
+    desc: "Options",
+    flags: {
+      jokes: {
+        category: "Gameplay",
+        title: "Jokes",
+        desc: "Tell jokes, {even} with braces",
+        default: false
+      }
+    }
+  }
+};
+function getFlag(flag) {
+  return defaultFlags.peacock.flags[flag];
+}
+"#;
+        let obj_str = extract_object(chunk0).unwrap();
+        assert!(obj_str.ends_with("}\n}"));
+        assert_eq!(flag_keys(&obj_str), vec!["jokes"]);
+    }
+
+    #[test]
+    fn quotes_in_comments_are_ignored() {
+        let flags_ts = r#"export const defaultFlags: Flags = {
+    // don't let this apostrophe open a string }
+    peacock: {
+        /* a "quoted { brace */
+        title: "Peacock",
+        desc: "Options",
+        flags: {
+            jokes: {
+                category: "Gameplay",
+                title: "Jokes",
+                desc: "Tell jokes",
+                default: false, // it's off
+            },
+        },
+    },
+}
+
+const other = { unrelated: true }
+"#;
+        let obj_str = extract_object(flags_ts).unwrap();
+        assert!(!obj_str.contains("unrelated"));
+        assert_eq!(flag_keys(&obj_str), vec!["jokes"]);
+    }
+
+    #[test]
+    fn diff_is_empty_when_values_match_defaults() {
+        let defaults = vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                option("mapDiscoveryState", "KEEP"),
+            ],
+        )];
+        assert!(diff_against_defaults(&defaults, &defaults).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_changed_values() {
+        let defaults = vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                option("mapDiscoveryState", "KEEP"),
+            ],
+        )];
+        let current = vec![section(
+            "peacock",
+            vec![option("jokes", "true"), option("mapDiscoveryState", "KEEP")],
+        )];
+        assert_eq!(
+            diff_against_defaults(&current, &defaults),
+            vec![OptionDiff {
+                section: "peacock".to_string(),
+                key: "jokes".to_string(),
+                value: "true".to_string(),
+                default: "false".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_skips_options_without_default() {
+        let defaults = vec![section("peacock", vec![option("jokes", "false")])];
+        let current = vec![
+            section(
+                "peacock",
+                vec![option("jokes", "false"), option("unknown", "1")],
+            ),
+            section("plugin", vec![option("jokes", "true")]),
+        ];
+        assert!(diff_against_defaults(&current, &defaults).is_empty());
+    }
+
+    #[test]
+    fn default_value_is_looked_up_by_section_and_key() {
+        let defaults = vec![section("peacock", vec![option("jokes", "false")])];
+        assert_eq!(
+            get_default_value(&defaults, "peacock", "jokes"),
+            Some("false")
+        );
+        assert_eq!(get_default_value(&defaults, "other", "jokes"), None);
+        assert_eq!(get_default_value(&defaults, "peacock", "missing"), None);
+    }
+
+    #[test]
+    fn validation_rejects_values_outside_definitions() {
+        let defaults = vec![section(
+            "peacock",
+            vec![
+                option("jokes", "false"),
+                choice("mapDiscoveryState", "KEEP", &["REVEALED", "KEEP"]),
+                option("leaderboardsHost", ""),
+            ],
+        )];
+        assert!(validate_peacock_options(&defaults, &defaults).is_empty());
+
+        let mut current = defaults.clone();
+        current[0].options[0].value = "maybe".to_string();
+        current[0].options[1].value = "FOGGY".to_string();
+        current[0].options[2].value = "https://example.com".to_string();
+        assert_eq!(validate_peacock_options(&current, &defaults).len(), 2);
+    }
 }
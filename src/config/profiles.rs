@@ -0,0 +1,214 @@
+use crate::config::peacock::{
+    IniEntry, PeacockCategory, apply_ini_entries, diff_against_defaults, parse_ini,
+    parse_peacock_defaults, save_peacock_config,
+};
+use crate::config::spear::{load_spear_config, save_spear_config};
+use crate::constants::SPEAR_PATH;
+use crate::error::{Context, SpearResult};
+
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The profile written to `options.ini` before Peacock starts. `None` means
+    /// `options.ini` is used as-is.
+    pub static ref ACTIVE_PROFILE: Mutex<Option<String>> =
+        Mutex::new(load_spear_config().active_profile);
+}
+
+pub fn get_profiles_dir() -> PathBuf {
+    SPEAR_PATH.join("profiles")
+}
+
+pub fn get_profile_path(name: &str) -> PathBuf {
+    get_profiles_dir().join(format!("{}.ini", name))
+}
+
+/// Profile names end up as file names, so only allow a conservative set of characters.
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+}
+
+pub fn list_profiles() -> Vec<String> {
+    let mut profiles: Vec<String> = fs::read_dir(get_profiles_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("ini"))
+                .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles
+}
+
+/// Returns only the entries that differ from `defaults`; everything else is
+/// inherited when the profile is loaded.
+pub fn profile_overrides(
+    categories: &[PeacockCategory],
+    defaults: &[PeacockCategory],
+) -> Vec<IniEntry> {
+    diff_against_defaults(categories, defaults)
+        .into_iter()
+        .map(|d| IniEntry {
+            section: d.section,
+            key: d.key,
+            value: d.value,
+        })
+        .collect()
+}
+
+pub fn load_profile(name: &str, defaults: &[PeacockCategory]) -> Vec<PeacockCategory> {
+    let path = get_profile_path(name);
+    log::info!("[+] Loading profile '{}' from {:?}", name, path);
+    let mut categories = defaults.to_vec();
+    match fs::read_to_string(&path) {
+        Ok(content) => apply_ini_entries(&mut categories, parse_ini(&content)),
+        Err(e) => log::error!("[!] Failed to read profile '{}': {}", name, e),
+    }
+    categories
+}
+
+pub fn save_profile(
+    name: &str,
+    categories: &[PeacockCategory],
+    defaults: &[PeacockCategory],
+) -> SpearResult<()> {
+    let path = get_profile_path(name);
+    let context = format!("Failed to save profile '{}'", name);
+    fs::create_dir_all(get_profiles_dir()).context(&context)?;
+    let mut content = format!(
+        "; spear profile '{}', only values differing from the defaults\n",
+        name
+    );
+    let mut current_section: Option<&str> = None;
+    let overrides = profile_overrides(categories, defaults);
+    for entry in &overrides {
+        if current_section != Some(entry.section.as_str()) {
+            content.push_str(&format!("[{}]\n", entry.section));
+            current_section = Some(entry.section.as_str());
+        }
+        content.push_str(&format!("{}={}\n", entry.key, entry.value));
+    }
+    crate::config::atomic::write_atomic(&path, &content).context(context)?;
+    log::info!(
+        "[+] Saved profile '{}' with {} override(s)",
+        name,
+        overrides.len()
+    );
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> SpearResult<()> {
+    fs::remove_file(get_profile_path(name))
+        .context(format!("Failed to delete profile '{}'", name))?;
+    let mut active = ACTIVE_PROFILE.lock().unwrap();
+    if active.as_deref() == Some(name) {
+        *active = None;
+        drop(active);
+        persist_active_profile(None);
+    }
+    Ok(())
+}
+
+pub fn set_active_profile(name: Option<String>) {
+    log::info!("[+] Active profile set to {:?}", name);
+    *ACTIVE_PROFILE.lock().unwrap() = name.clone();
+    persist_active_profile(name);
+}
+
+fn persist_active_profile(name: Option<String>) {
+    let mut config = load_spear_config();
+    config.active_profile = name;
+    if let Err(e) = save_spear_config(&config) {
+        log::error!("[!] Failed to save active profile: {}", e);
+    }
+}
+
+/// Moves to the profile after the active one, wrapping around to `None`.
+pub fn cycle_active_profile() -> Option<String> {
+    let profiles = list_profiles();
+    let current = ACTIVE_PROFILE.lock().unwrap().clone();
+    let next = match current.and_then(|c| profiles.iter().position(|p| *p == c)) {
+        Some(idx) => profiles.get(idx + 1).cloned(),
+        None => profiles.first().cloned(),
+    };
+    set_active_profile(next.clone());
+    next
+}
+
+/// Writes the active profile into Peacock's `options.ini`. Called right before
+/// the node server is started.
+pub fn apply_active_profile() {
+    let Some(name) = load_spear_config().active_profile else {
+        log::info!("[+] No active profile, using options.ini as-is");
+        return;
+    };
+    if !get_profile_path(&name).exists() {
+        log::error!("[!] Active profile '{}' does not exist", name);
+        return;
+    }
+    let defaults = parse_peacock_defaults();
+    let categories = load_profile(&name, &defaults);
+    match save_peacock_config(&categories) {
+        Ok(_) => log::info!("[+] Applied profile '{}' to options.ini", name),
+        Err(e) => log::error!("[!] Failed to apply profile '{}': {}", name, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_support::{option, section};
+
+    fn defaults() -> Vec<PeacockCategory> {
+        vec![section(
+            "peacock",
+            vec![
+                option("liveSplit", "false"),
+                option("elusivesAreShown", "false"),
+            ],
+        )]
+    }
+
+    #[test]
+    fn overrides_only_contain_changed_values() {
+        let defaults = defaults();
+        let mut categories = defaults.clone();
+        categories[0].options[0].value = "true".to_string();
+        assert_eq!(
+            profile_overrides(&categories, &defaults),
+            vec![IniEntry {
+                section: "peacock".to_string(),
+                key: "liveSplit".to_string(),
+                value: "true".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_keys_inherit_defaults() {
+        let mut categories = defaults();
+        apply_ini_entries(&mut categories, parse_ini("[peacock]\nliveSplit=true\n"));
+        assert_eq!(categories[0].options[0].value, "true");
+        assert_eq!(categories[0].options[1].value, "false");
+    }
+
+    #[test]
+    fn profile_names_are_restricted() {
+        assert!(is_valid_profile_name("speedrun practice"));
+        assert!(is_valid_profile_name("plugin-dev_2"));
+        assert!(!is_valid_profile_name(""));
+        assert!(!is_valid_profile_name("   "));
+        assert!(!is_valid_profile_name("../options"));
+        assert!(!is_valid_profile_name("a/b"));
+    }
+}
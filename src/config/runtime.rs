@@ -0,0 +1,337 @@
+use crate::config::spear::{SpearConfig, is_valid_repo, load_spear_config};
+
+use log::LevelFilter;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingSource {
+    Default,
+    Config,
+    Env,
+    Args,
+}
+
+impl SettingSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SettingSource::Default => "default",
+            SettingSource::Config => "config.toml",
+            SettingSource::Env => "environment",
+            SettingSource::Args => "command line",
+        }
+    }
+}
+
+/// Behaviour for the current launch, after all layers have been applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeSettings {
+    pub peacock_github_repo: String,
+    pub skip_overlay: bool,
+    pub force_reinstall: bool,
+    pub log_level: LevelFilter,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedSettings {
+    pub settings: RuntimeSettings,
+    /// `(setting name, value, source)` for display in the settings window.
+    pub entries: Vec<(&'static str, String, SettingSource)>,
+    pub warnings: Vec<String>,
+}
+
+/// The names below double as `SPEAR_<NAME>` env vars and `--spear-<name>` args.
+const REPO: &str = "repo";
+const SKIP_OVERLAY: &str = "skip-overlay";
+const REINSTALL: &str = "reinstall";
+const LOG_LEVEL: &str = "log-level";
+/// Only take a value as `--spear-<name>=value`; a bare flag means `true`.
+const BOOL_SETTINGS: &[&str] = &[SKIP_OVERLAY, REINSTALL];
+
+fn env_name(name: &str) -> String {
+    format!("SPEAR_{}", name.replace('-', "_").to_uppercase())
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" | "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Finds `--spear-<name>=value`, `--spear-<name> value` or a bare
+/// `--spear-<name>` (returned as an empty string). The last occurrence wins.
+/// Booleans never take the next argument, and nothing takes one starting with
+/// `-`, so launcher arguments such as `-AUTH_PASSWORD=...` are left alone.
+fn find_arg(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--spear-{}", name);
+    let mut found = None;
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+            found = Some(value.to_string());
+        } else if *arg == flag {
+            let takes_value = !BOOL_SETTINGS.contains(&name);
+            match args
+                .get(i + 1)
+                .filter(|next| takes_value && !next.starts_with('-'))
+            {
+                Some(next) => {
+                    found = Some(next.clone());
+                    i += 1;
+                }
+                None => found = Some(String::new()),
+            }
+        }
+        i += 1;
+    }
+    found
+}
+
+struct Layers<'a> {
+    env: &'a dyn Fn(&str) -> Option<String>,
+    args: &'a [String],
+    warnings: Vec<String>,
+}
+
+impl Layers<'_> {
+    /// Returns the highest-priority override for `name` that `parse` accepts.
+    fn lookup<T>(
+        &mut self,
+        name: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Option<(T, SettingSource)> {
+        let mut result = None;
+        let candidates = [
+            ((self.env)(&env_name(name)), SettingSource::Env),
+            (find_arg(self.args, name), SettingSource::Args),
+        ];
+        for (raw, source) in candidates {
+            let Some(raw) = raw else { continue };
+            match parse(&raw) {
+                Some(value) => result = Some((value, source)),
+                // Command lines can carry launcher auth tokens, so their
+                // values never go into the log.
+                None if source == SettingSource::Args => {
+                    self.warnings
+                        .push(format!("Ignoring invalid {} from {}", name, source.label()))
+                }
+                None => self.warnings.push(format!(
+                    "Ignoring invalid {} from {}: '{}'",
+                    name,
+                    source.label(),
+                    raw
+                )),
+            }
+        }
+        result
+    }
+}
+
+pub fn resolve_runtime_settings_from(
+    config: &SpearConfig,
+    env: &dyn Fn(&str) -> Option<String>,
+    args: &[String],
+) -> ResolvedSettings {
+    let defaults = SpearConfig::default();
+    let mut layers = Layers {
+        env,
+        args,
+        warnings: vec![],
+    };
+    let mut entries = vec![];
+
+    let (mut repo, mut repo_source) = (defaults.peacock_github_repo, SettingSource::Default);
+    if config.peacock_github_repo != repo {
+        (repo, repo_source) = (config.peacock_github_repo.clone(), SettingSource::Config);
+    }
+    let parse_repo = |v: &str| {
+        let v = v.trim();
+        is_valid_repo(v).then(|| v.to_string())
+    };
+    if let Some(found) = layers.lookup(REPO, parse_repo) {
+        (repo, repo_source) = found;
+    }
+    entries.push((REPO, repo.clone(), repo_source));
+
+    let (skip_overlay, skip_source) = layers
+        .lookup(SKIP_OVERLAY, parse_bool)
+        .unwrap_or((false, SettingSource::Default));
+    entries.push((SKIP_OVERLAY, skip_overlay.to_string(), skip_source));
+
+    let (force_reinstall, reinstall_source) = layers
+        .lookup(REINSTALL, parse_bool)
+        .unwrap_or((false, SettingSource::Default));
+    entries.push((REINSTALL, force_reinstall.to_string(), reinstall_source));
+
+    let parse_level = |v: &str| LevelFilter::from_str(v.trim()).ok();
+    let (mut log_level, mut level_source) = (LevelFilter::Info, SettingSource::Default);
+    if let Some(configured) = &config.log_level {
+        match parse_level(configured) {
+            Some(level) => (log_level, level_source) = (level, SettingSource::Config),
+            None => layers.warnings.push(format!(
+                "Ignoring invalid log-level from config.toml: '{}'",
+                configured
+            )),
+        }
+    }
+    if let Some(found) = layers.lookup(LOG_LEVEL, parse_level) {
+        (log_level, level_source) = found;
+    }
+    entries.push((LOG_LEVEL, log_level.to_string(), level_source));
+
+    ResolvedSettings {
+        settings: RuntimeSettings {
+            peacock_github_repo: repo,
+            skip_overlay,
+            force_reinstall,
+            log_level,
+        },
+        entries,
+        warnings: layers.warnings,
+    }
+}
+
+/// Resolves against the current `config.toml`, the process environment and
+/// the host process command line.
+pub fn resolve_runtime_settings() -> ResolvedSettings {
+    let args: Vec<String> = std::env::args().collect();
+    resolve_runtime_settings_from(
+        &load_spear_config(),
+        &|name| std::env::var(name).ok(),
+        &args,
+    )
+}
+
+pub fn get_peacock_repo() -> String {
+    resolve_runtime_settings().settings.peacock_github_repo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn resolve(config: &SpearConfig, env: &[(&str, &str)], args: &[&str]) -> ResolvedSettings {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        resolve_runtime_settings_from(config, &|name| env.get(name).cloned(), &args)
+    }
+
+    #[test]
+    fn defaults_apply_without_overrides() {
+        let resolved = resolve(&SpearConfig::default(), &[], &["Launcher.exe"]);
+        assert_eq!(
+            resolved.settings,
+            RuntimeSettings {
+                peacock_github_repo: "thepeacockproject/peacock".to_string(),
+                skip_overlay: false,
+                force_reinstall: false,
+                log_level: LevelFilter::Info,
+            }
+        );
+        assert!(
+            resolved
+                .entries
+                .iter()
+                .all(|(_, _, source)| *source == SettingSource::Default)
+        );
+    }
+
+    #[test]
+    fn layers_override_in_order() {
+        let config = SpearConfig {
+            peacock_github_repo: "config/peacock".to_string(),
+            log_level: Some("warn".to_string()),
+            ..SpearConfig::default()
+        };
+
+        let resolved = resolve(&config, &[], &[]);
+        assert_eq!(resolved.settings.peacock_github_repo, "config/peacock");
+        assert_eq!(resolved.settings.log_level, LevelFilter::Warn);
+
+        let resolved = resolve(&config, &[("SPEAR_REPO", "env/peacock")], &[]);
+        assert_eq!(resolved.settings.peacock_github_repo, "env/peacock");
+        assert_eq!(resolved.entries[0].2, SettingSource::Env);
+
+        let resolved = resolve(
+            &config,
+            &[("SPEAR_REPO", "env/peacock"), ("SPEAR_LOG_LEVEL", "error")],
+            &["--spear-repo", "args/peacock", "--spear-log-level=debug"],
+        );
+        assert_eq!(resolved.settings.peacock_github_repo, "args/peacock");
+        assert_eq!(resolved.settings.log_level, LevelFilter::Debug);
+        assert_eq!(resolved.entries[0].2, SettingSource::Args);
+    }
+
+    #[test]
+    fn bare_flags_enable_booleans() {
+        let resolved = resolve(
+            &SpearConfig::default(),
+            &[],
+            &["Launcher.exe", "--spear-skip-overlay", "--spear-reinstall"],
+        );
+        assert!(resolved.settings.skip_overlay);
+        assert!(resolved.settings.force_reinstall);
+    }
+
+    #[test]
+    fn bare_flags_leave_launcher_arguments_alone() {
+        let resolved = resolve(
+            &SpearConfig::default(),
+            &[],
+            &[
+                "Launcher.exe",
+                "--spear-skip-overlay",
+                "-AUTH_PASSWORD=secret-token",
+                "--spear-repo",
+                "-AUTH_TYPE=exchangecode",
+            ],
+        );
+        assert!(resolved.settings.skip_overlay);
+        assert_eq!(resolved.entries[1].2, SettingSource::Args);
+        assert_eq!(
+            resolved.settings.peacock_github_repo,
+            "thepeacockproject/peacock"
+        );
+        assert_eq!(resolved.warnings.len(), 1);
+        assert!(
+            resolved
+                .warnings
+                .iter()
+                .all(|w| !w.contains("secret") && !w.contains("AUTH"))
+        );
+    }
+
+    #[test]
+    fn env_booleans_accept_common_spellings() {
+        for (value, expected) in [("1", true), ("yes", true), ("off", false), ("FALSE", false)] {
+            let resolved = resolve(
+                &SpearConfig::default(),
+                &[("SPEAR_SKIP_OVERLAY", value)],
+                &[],
+            );
+            assert_eq!(resolved.settings.skip_overlay, expected, "value: {}", value);
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_ignored_with_a_warning() {
+        let resolved = resolve(
+            &SpearConfig::default(),
+            &[("SPEAR_LOG_LEVEL", "loud"), ("SPEAR_REPO", "not-a-repo")],
+            &["--spear-reinstall=maybe"],
+        );
+        assert_eq!(resolved.settings.log_level, LevelFilter::Info);
+        assert_eq!(
+            resolved.settings.peacock_github_repo,
+            "thepeacockproject/peacock"
+        );
+        assert!(!resolved.settings.force_reinstall);
+        assert_eq!(resolved.warnings.len(), 3);
+    }
+}
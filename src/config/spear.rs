@@ -1,25 +1,162 @@
+use crate::error::{Context, SpearResult};
+
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Bump this and append a step to [`MIGRATIONS`] whenever the on-disk layout changes.
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
 pub struct SpearConfig {
+    pub config_version: u32,
     pub peacock_github_repo: String,
+    pub active_profile: Option<String>,
+    /// Moves everything except `config.toml` elsewhere; applied on next start.
+    pub data_dir: Option<String>,
+    pub log_level: Option<String>,
 }
 
 impl Default for SpearConfig {
     fn default() -> Self {
         Self {
+            config_version: CURRENT_CONFIG_VERSION,
             peacock_github_repo: "thepeacockproject/peacock".to_string(),
+            active_profile: None,
+            data_dir: None,
+            log_level: None,
+        }
+    }
+}
+
+lazy_static! {
+    /// Set when `config.toml` could not be parsed, so the settings window can
+    /// tell the user instead of silently falling back to defaults.
+    pub static ref CONFIG_LOAD_ERROR: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// GitHub repos are referenced as `owner/name`.
+pub fn is_valid_repo(repo: &str) -> bool {
+    let mut parts = repo.split('/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(owner), Some(name), None) => {
+            !owner.is_empty() && !name.is_empty() && !repo.contains(char::is_whitespace)
         }
+        _ => false,
     }
 }
 
+/// Checks a config before it is written, returning one message per problem.
+pub fn validate_spear_config(config: &SpearConfig) -> Vec<String> {
+    let mut errors = vec![];
+    if !is_valid_repo(&config.peacock_github_repo) {
+        errors.push(format!(
+            "Peacock GitHub '{}' must be in owner/name form",
+            config.peacock_github_repo
+        ));
+    }
+    if let Some(dir) = &config.data_dir {
+        if !Path::new(dir).is_absolute() {
+            errors.push(format!(
+                "Custom data directory '{}' must be an absolute path",
+                dir
+            ));
+        }
+    }
+    errors
+}
+
+type Migration = fn(&mut toml::Table);
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+/// v0 had no `config_version` and users often pasted the full GitHub URL as the repo.
+fn migrate_v0_to_v1(table: &mut toml::Table) {
+    if let Some(toml::Value::String(repo)) = table.get_mut("peacock_github_repo") {
+        let normalized = repo
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("github.com/")
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_string();
+        *repo = normalized;
+    }
+}
+
+/// Parses `config.toml`, running any pending migrations. Returns the config and
+/// the version it was stored as.
+pub fn parse_spear_config(content: &str) -> Result<(SpearConfig, u32), String> {
+    let mut table: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+    let stored_version = match table.get("config_version") {
+        None => 0,
+        Some(toml::Value::Integer(v)) => u32::try_from(*v).map_err(|_| {
+            format!(
+                "config_version must be a non-negative integer up to {}, got {}",
+                u32::MAX,
+                v
+            )
+        })?,
+        Some(v) => {
+            return Err(format!(
+                "config_version must be a non-negative integer, got {}",
+                v
+            ));
+        }
+    };
+    if stored_version > CURRENT_CONFIG_VERSION {
+        log::error!(
+            "[!] config.toml is version {}, newer than supported {}; loading known fields only",
+            stored_version,
+            CURRENT_CONFIG_VERSION
+        );
+    }
+    for version in stored_version..CURRENT_CONFIG_VERSION {
+        log::info!(
+            "[+] Migrating Spear config from v{} to v{}",
+            version,
+            version + 1
+        );
+        MIGRATIONS[version as usize](&mut table);
+        table.insert(
+            "config_version".to_string(),
+            toml::Value::Integer(version as i64 + 1),
+        );
+    }
+    let config: SpearConfig = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+    Ok((config, stored_version))
+}
+
 pub fn get_spear_config_path() -> PathBuf {
-    let mut path = dirs::data_local_dir().unwrap();
-    path.push("spear");
-    path.push("config.toml");
-    path
+    crate::core::paths::SPEAR_PATHS
+        .config_dir
+        .join("config.toml")
+}
+
+fn backup_spear_config(reason: &str) -> Option<PathBuf> {
+    let path = get_spear_config_path();
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let backup_path = crate::constants::SPEAR_PATH
+        .join("backups")
+        .join(format!("config-{}-{}.toml", reason, timestamp));
+    fs::create_dir_all(backup_path.parent()?).ok()?;
+    match fs::copy(&path, &backup_path) {
+        Ok(_) => {
+            log::info!("[+] Backed up Spear config to {:?}", backup_path);
+            Some(backup_path)
+        }
+        Err(e) => {
+            log::error!("[!] Failed to back up Spear config: {}", e);
+            None
+        }
+    }
 }
 
 pub fn load_spear_config() -> SpearConfig {
@@ -27,23 +164,147 @@ pub fn load_spear_config() -> SpearConfig {
     log::info!("[+] Loading Spear config from: {:?}", path);
     if path.exists() {
         let content = fs::read_to_string(&path).unwrap_or_default();
-        let config: SpearConfig = toml::from_str(&content).unwrap_or_default();
-        log::info!(
-            "[+] Loaded Spear config: peacock_github_repo={}",
-            config.peacock_github_repo
-        );
-        config
+        match parse_spear_config(&content) {
+            Ok((config, stored_version)) => {
+                *CONFIG_LOAD_ERROR.lock().unwrap() = None;
+                if stored_version < CURRENT_CONFIG_VERSION {
+                    backup_spear_config(&format!("v{}", stored_version));
+                    if let Err(e) = save_spear_config(&config) {
+                        log::error!("[!] Failed to save migrated Spear config: {}", e);
+                    }
+                }
+                log::info!(
+                    "[+] Loaded Spear config: peacock_github_repo={}",
+                    config.peacock_github_repo
+                );
+                config
+            }
+            Err(e) => {
+                log::error!("[!] Failed to parse Spear config: {}", e);
+                let mut load_error = CONFIG_LOAD_ERROR.lock().unwrap();
+                if load_error.is_none() {
+                    let message = match backup_spear_config("invalid") {
+                        Some(backup) => format!(
+                            "config.toml could not be read ({}). Defaults are in use; the original was saved to {}",
+                            e,
+                            backup.display()
+                        ),
+                        None => {
+                            format!("config.toml could not be read ({}). Defaults are in use", e)
+                        }
+                    };
+                    *load_error = Some(message);
+                }
+                SpearConfig::default()
+            }
+        }
     } else {
         log::info!("[+] Spear config not found, creating default");
         let default = SpearConfig::default();
-        save_spear_config(&default);
+        if let Err(e) = save_spear_config(&default) {
+            log::error!("[!] Failed to save default Spear config: {}", e);
+        }
         default
     }
 }
 
-pub fn save_spear_config(config: &SpearConfig) {
-    let path = get_spear_config_path();
-    fs::create_dir_all(path.parent().unwrap()).unwrap();
-    let content = toml::to_string(config).unwrap();
-    fs::write(&path, content).unwrap();
+pub fn save_spear_config(config: &SpearConfig) -> SpearResult<()> {
+    let content = toml::to_string(config).context("Failed to serialize config.toml")?;
+    crate::config::atomic::write_atomic(&get_spear_config_path(), &content)
+        .context("Failed to save config.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v0_config_is_migrated_to_current() {
+        let (config, stored_version) =
+            parse_spear_config("peacock_github_repo = \"thepeacockproject/peacock\"\n").unwrap();
+        assert_eq!(stored_version, 0);
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.peacock_github_repo, "thepeacockproject/peacock");
+    }
+
+    #[test]
+    fn v0_to_v1_normalizes_repo_urls() {
+        for input in [
+            "https://github.com/someone/peacock",
+            "https://github.com/someone/peacock/",
+            "github.com/someone/peacock.git",
+            " someone/peacock ",
+        ] {
+            let mut table = toml::Table::new();
+            table.insert(
+                "peacock_github_repo".to_string(),
+                toml::Value::String(input.to_string()),
+            );
+            migrate_v0_to_v1(&mut table);
+            assert_eq!(
+                table["peacock_github_repo"].as_str(),
+                Some("someone/peacock"),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let (config, _) = parse_spear_config("config_version = 1\n").unwrap();
+        assert_eq!(config, SpearConfig::default());
+    }
+
+    #[test]
+    fn current_config_is_not_migrated() {
+        let content = toml::to_string(&SpearConfig::default()).unwrap();
+        let (config, stored_version) = parse_spear_config(&content).unwrap();
+        assert_eq!(stored_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config, SpearConfig::default());
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let (config, _) =
+            parse_spear_config("config_version = 1\nremoved_option = true\n").unwrap();
+        assert_eq!(config, SpearConfig::default());
+    }
+
+    #[test]
+    fn parse_errors_are_reported() {
+        assert!(parse_spear_config("peacock_github_repo = ").is_err());
+        assert!(parse_spear_config("peacock_github_repo = 5").is_err());
+        assert!(parse_spear_config("config_version = \"one\"").is_err());
+        assert!(parse_spear_config("config_version = -1").is_err());
+        assert!(parse_spear_config("config_version = 4294967297").is_err());
+    }
+
+    #[test]
+    fn repos_must_be_owner_and_name() {
+        assert!(is_valid_repo("thepeacockproject/peacock"));
+        assert!(!is_valid_repo("thepeacockproject"));
+        assert!(!is_valid_repo("thepeacockproject/"));
+        assert!(!is_valid_repo("/peacock"));
+        assert!(!is_valid_repo("a/b/c"));
+        assert!(!is_valid_repo("thepeacock project/peacock"));
+    }
+
+    #[test]
+    fn validation_reports_each_problem() {
+        assert!(validate_spear_config(&SpearConfig::default()).is_empty());
+        let mut config = SpearConfig {
+            peacock_github_repo: "thepeacockproject/".to_string(),
+            data_dir: Some("spear-data".to_string()),
+            ..SpearConfig::default()
+        };
+        assert_eq!(validate_spear_config(&config).len(), 2);
+        config.data_dir = Some(std::env::temp_dir().to_string_lossy().to_string());
+        assert_eq!(validate_spear_config(&config).len(), 1);
+    }
+
+    #[test]
+    fn every_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len(), CURRENT_CONFIG_VERSION as usize);
+    }
 }
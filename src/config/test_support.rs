@@ -0,0 +1,28 @@
+//! Peacock option fixtures shared by the config tests.
+
+use crate::config::peacock::{PeacockCategory, PeacockOption};
+
+pub fn option(key: &str, value: &str) -> PeacockOption {
+    PeacockOption {
+        key: key.to_string(),
+        value: value.to_string(),
+        category: "Gameplay".to_string(),
+        description: format!("{} description", key),
+        possible_values: None,
+    }
+}
+
+/// An option that can only take one of `possible_values`.
+pub fn choice(key: &str, value: &str, possible_values: &[&str]) -> PeacockOption {
+    PeacockOption {
+        possible_values: Some(possible_values.iter().map(|s| s.to_string()).collect()),
+        ..option(key, value)
+    }
+}
+
+pub fn section(name: &str, options: Vec<PeacockOption>) -> PeacockCategory {
+    PeacockCategory {
+        name: name.to_string(),
+        options,
+    }
+}
@@ -0,0 +1,198 @@
+use crate::config::peacock::{IniEntry, PeacockCategory, apply_ini_entries};
+use crate::config::spear::SpearConfig;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// A config file the settings window edits. Remembers what was last loaded or
+/// written so edits made by other programs are noticed instead of overwritten.
+#[derive(Clone, Debug)]
+pub struct WatchedFile {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+    content: Option<String>,
+}
+
+impl WatchedFile {
+    pub fn new(path: PathBuf) -> Self {
+        let mut file = Self {
+            path,
+            modified: None,
+            content: None,
+        };
+        file.mark_synced();
+        file
+    }
+
+    /// Records the file as it is on disk right now as the known state.
+    pub fn mark_synced(&mut self) {
+        self.modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        self.content = fs::read_to_string(&self.path).ok();
+    }
+
+    /// Cheap poll: only reads the file when its mtime moved, and only reports a
+    /// change when the content actually differs from the known state.
+    pub fn changed_on_disk(&mut self) -> bool {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        self.has_conflict()
+    }
+
+    /// Full content comparison, used right before saving since an edit can
+    /// land between two polls or within the same mtime tick.
+    pub fn has_conflict(&self) -> bool {
+        fs::read_to_string(&self.path).ok() != self.content
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeResult<K, V> {
+    pub merged: BTreeMap<K, V>,
+    /// Keys changed differently on both sides. The local value is kept.
+    pub conflicts: Vec<K>,
+}
+
+/// Three-way merge of `local` and `external` edits made since `base`. Values
+/// only one side changed take that side; a missing key means the value was removed.
+pub fn merge_changes<K: Ord + Clone, V: PartialEq + Clone>(
+    base: &BTreeMap<K, V>,
+    local: &BTreeMap<K, V>,
+    external: &BTreeMap<K, V>,
+) -> MergeResult<K, V> {
+    let keys: BTreeSet<&K> = base
+        .keys()
+        .chain(local.keys())
+        .chain(external.keys())
+        .collect();
+    let mut merged = BTreeMap::new();
+    let mut conflicts = vec![];
+    for key in keys {
+        let (b, l, e) = (base.get(key), local.get(key), external.get(key));
+        let value = if l == b {
+            e
+        } else if e == b || e == l {
+            l
+        } else {
+            conflicts.push(key.clone());
+            l
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    MergeResult { merged, conflicts }
+}
+
+pub fn peacock_values(categories: &[PeacockCategory]) -> BTreeMap<String, String> {
+    categories
+        .iter()
+        .flat_map(|c| {
+            c.options
+                .iter()
+                .map(|o| (format!("{}.{}", c.name, o.key), o.value.clone()))
+        })
+        .collect()
+}
+
+/// Writes merged `section.key` values back onto `categories`.
+pub fn apply_peacock_values(categories: &mut [PeacockCategory], values: &BTreeMap<String, String>) {
+    let entries = values
+        .iter()
+        .filter_map(|(name, value)| {
+            let (section, key) = name.split_once('.')?;
+            Some(IniEntry {
+                section: section.to_string(),
+                key: key.to_string(),
+                value: value.clone(),
+            })
+        })
+        .collect();
+    apply_ini_entries(categories, entries);
+}
+
+pub fn spear_values(config: &SpearConfig) -> BTreeMap<String, toml::Value> {
+    match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => table.into_iter().collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+pub fn spear_from_values(
+    values: BTreeMap<String, toml::Value>,
+) -> Result<SpearConfig, toml::de::Error> {
+    toml::Value::Table(values.into_iter().collect()).try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_takes_each_sides_edits() {
+        let base = map(&[("a", "1"), ("b", "1"), ("c", "1")]);
+        let local = map(&[("a", "2"), ("b", "1"), ("c", "1")]);
+        let external = map(&[("a", "1"), ("b", "3"), ("c", "1")]);
+        let result = merge_changes(&base, &local, &external);
+        assert_eq!(result.merged, map(&[("a", "2"), ("b", "3"), ("c", "1")]));
+        assert!(result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn conflicting_edits_keep_local_value() {
+        let base = map(&[("a", "1")]);
+        let result = merge_changes(&base, &map(&[("a", "2")]), &map(&[("a", "3")]));
+        assert_eq!(result.merged, map(&[("a", "2")]));
+        assert_eq!(result.conflicts, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn identical_edits_are_not_conflicts() {
+        let base = map(&[("a", "1")]);
+        let result = merge_changes(&base, &map(&[("a", "2")]), &map(&[("a", "2")]));
+        assert_eq!(result.merged, map(&[("a", "2")]));
+        assert!(result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn external_additions_and_removals_are_applied() {
+        let base = map(&[("a", "1"), ("b", "1")]);
+        let result = merge_changes(&base, &base, &map(&[("a", "1"), ("c", "1")]));
+        assert_eq!(result.merged, map(&[("a", "1"), ("c", "1")]));
+    }
+
+    #[test]
+    fn spear_config_round_trips_through_values() {
+        let config = SpearConfig {
+            active_profile: Some("speedrun".to_string()),
+            ..SpearConfig::default()
+        };
+        assert_eq!(spear_from_values(spear_values(&config)).unwrap(), config);
+    }
+
+    #[test]
+    fn changes_on_disk_are_detected() {
+        let dir = TempDir::new("watch");
+        let path = dir.join("options.ini");
+        fs::write(&path, "[peacock]\njokes=false\n").unwrap();
+        let mut file = WatchedFile::new(path.clone());
+        assert!(!file.changed_on_disk());
+        assert!(!file.has_conflict());
+        fs::write(&path, "[peacock]\njokes=true\n").unwrap();
+        assert!(file.has_conflict());
+        file.mark_synced();
+        assert!(!file.has_conflict());
+    }
+}
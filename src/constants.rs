@@ -1,5 +1,3 @@
-#[allow(dead_code)]
-use dirs;
 use lazy_static::lazy_static;
 use std::path::PathBuf;
 
@@ -9,16 +7,10 @@ pub const SETTINGS_ICON_HASH: &str =
 
 pub const DWMWA_WINDOW_CORNER_PREFERENCE: u32 = 33;
 pub const DWMWCP_DONOTROUND: u32 = 1;
-pub const WINDOW_WIDTH: f32 = 222.0;
-pub const WINDOW_HEIGHT: f32 = 39.0;
-pub const WINDOW_POS_X: f32 = 79.0;
-pub const WINDOW_POS_Y: f32 = 89.0;
-pub const ICON_POS_X: f32 = 9.0;
-pub const ICON_POS_Y: f32 = 7.0;
 
 pub const TARGET_WIDTH: u32 = 608;
 pub const TARGET_HEIGHT: u32 = 344;
 
 lazy_static! {
-    pub static ref SPEAR_PATH: PathBuf = dirs::data_local_dir().unwrap().join("spear");
+    pub static ref SPEAR_PATH: PathBuf = crate::core::paths::SPEAR_PATHS.data_dir.clone();
 }
@@ -0,0 +1,323 @@
+use crate::config::peacock::{get_installed_peacock_version, get_peacock_config_path};
+use crate::config::spear::get_spear_config_path;
+use crate::constants::SPEAR_PATH;
+use crate::core::logs::{get_logs_dir, list_logs};
+use crate::core::paths::SPEAR_PATHS;
+use crate::core::peacock_server::{ServerStatus, get_crash_reports_dir, peacock_server_status};
+use crate::error::{Context, SpearResult};
+
+use lazy_static::lazy_static;
+use pelite::pe64::{Pe, PeFile};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::write::SimpleFileOptions;
+
+/// Log files of each kind that go into a bundle, newest first.
+const LOGS_PER_KIND: usize = 3;
+const CRASH_REPORTS: usize = 5;
+/// Config keys whose values never leave the machine: the whole key, or its
+/// last `_`-separated word, as in `github_token` or `api_key`.
+const SECRET_KEYS: &[&str] = &["token", "secret", "password", "auth", "key", "apikey"];
+
+lazy_static! {
+    /// Result of the last bundle started from the settings window.
+    pub static ref DIAGNOSTICS_STATUS: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn get_diagnostics_dir() -> PathBuf {
+    SPEAR_PATH.join("diagnostics")
+}
+
+/// Replaces the user's home folder with `%USERPROFILE%`, so paths don't leak
+/// the account name. Applied to every file in a bundle.
+pub fn redact_home(content: &str, home: Option<&str>) -> String {
+    match home.filter(|h| !h.is_empty()) {
+        Some(home) => content
+            .replace(home, "%USERPROFILE%")
+            .replace(&home.replace('\\', "\\\\"), "%USERPROFILE%"),
+        None => content.to_string(),
+    }
+}
+
+/// Blanks values of secret-looking keys, then hides the home folder like
+/// [`redact_home`].
+pub fn redact_config(content: &str, home: Option<&str>) -> String {
+    let mut redacted = String::new();
+    for line in content.lines() {
+        let secret = line.split_once('=').is_some_and(|(key, _)| {
+            let key = key.trim().to_lowercase();
+            let last_word = key.rsplit('_').next().unwrap_or(&key);
+            !key.starts_with('#') && SECRET_KEYS.contains(&last_word)
+        });
+        let line = if secret {
+            let (key, _) = line.split_once('=').unwrap();
+            format!("{}= \"<redacted>\"", key)
+        } else {
+            line.to_string()
+        };
+        redacted.push_str(&line);
+        redacted.push('\n');
+    }
+    redact_home(&redacted, home)
+}
+
+/// The newest `count` of `paths`, which are sorted oldest first.
+fn newest(mut paths: Vec<PathBuf>, count: usize) -> Vec<PathBuf> {
+    paths.reverse();
+    paths.truncate(count);
+    paths
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn file_version(path: &Path) -> Option<String> {
+    let map = pelite::FileMap::open(path).ok()?;
+    let pe = PeFile::from_bytes(map.as_ref()).ok()?;
+    let fixed = pe.resources().ok()?.version_info().ok()?.fixed()?;
+    let v = fixed.dwFileVersion;
+    Some(format!("{}.{}.{}.{}", v.Major, v.Minor, v.Patch, v.Build))
+}
+
+/// Launcher.exe and Retail\HITMAN3.exe, found from whichever one is running.
+fn game_executables() -> Vec<PathBuf> {
+    let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+    else {
+        return vec![];
+    };
+    let game_dir = if exe_dir.join("Launcher.exe").exists() {
+        exe_dir
+    } else {
+        exe_dir.parent().map(Path::to_path_buf).unwrap_or(exe_dir)
+    };
+    [
+        game_dir.join("Launcher.exe"),
+        game_dir.join("Retail").join("HITMAN3.exe"),
+    ]
+    .into_iter()
+    .filter(|p| p.exists())
+    .collect()
+}
+
+fn summary() -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "spear {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "created: {}", chrono::Local::now().to_rfc3339());
+    let _ = writeln!(
+        out,
+        "host: {}",
+        std::env::current_exe()
+            .map(|p| p.display().to_string())
+            .unwrap_or("unknown".to_string())
+    );
+    let _ = writeln!(
+        out,
+        "data dir: {} (from {})",
+        SPEAR_PATHS.data_dir.display(),
+        SPEAR_PATHS.source.label()
+    );
+    let _ = writeln!(
+        out,
+        "peacock: {}",
+        get_installed_peacock_version().unwrap_or("not installed".to_string())
+    );
+    let _ = writeln!(
+        out,
+        "peacock server: {}",
+        match peacock_server_status() {
+            ServerStatus::NotRunning => "not running".to_string(),
+            ServerStatus::Running(info) => format!(
+                "running (PID {}, {})",
+                info.pid,
+                info.health.map(|h| h.label()).unwrap_or("starting")
+            ),
+            ServerStatus::Restarting => "restarting".to_string(),
+            ServerStatus::Failed(reason) => format!("failed: {}", reason),
+        }
+    );
+
+    let _ = writeln!(out, "\n[installation]");
+    let problems = crate::core::resources::check_installation(&SPEAR_PATH.join("peacock"));
+    if problems.is_empty() {
+        let _ = writeln!(out, "ok");
+    }
+    for problem in problems {
+        let _ = writeln!(out, "{}", problem);
+    }
+
+    let _ = writeln!(out, "\n[executables]");
+    for exe in game_executables() {
+        let _ = writeln!(
+            out,
+            "{}: version {}, blake3 {}",
+            exe.display(),
+            file_version(&exe).unwrap_or("unknown".to_string()),
+            hash_file(&exe).unwrap_or_else(|e| format!("unreadable ({})", e))
+        );
+    }
+
+    let _ = writeln!(out, "\n[patterns]");
+    match crate::core::injection::scan_patterns() {
+        Some(matches) => {
+            for m in matches {
+                let _ = match m.offset {
+                    Some(offset) => writeln!(out, "{}: found at offset 0x{:x}", m.name, offset),
+                    None => writeln!(out, "{}: not found", m.name),
+                };
+            }
+        }
+        None => {
+            let _ = writeln!(out, "could not read the .text section");
+        }
+    }
+    out
+}
+
+/// Zips logs, redacted config, `options.ini`, version and a summary of the
+/// install into `SPEAR_PATH/diagnostics/spear-diagnostics-<timestamp>.zip`.
+pub fn create_diagnostics_bundle() -> SpearResult<PathBuf> {
+    let dir = get_diagnostics_dir();
+    fs::create_dir_all(&dir).context("Failed to create the diagnostics folder")?;
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let path = dir.join(format!("spear-diagnostics-{}.zip", timestamp));
+    log::info!("[+] Creating diagnostics bundle at {:?}", path);
+
+    let file = File::create(&path).context(format!("Failed to create {}", path.display()))?;
+    write_bundle(file).context("Failed to create diagnostics bundle")?;
+    log::info!("[+] Diagnostics bundle created");
+    Ok(path)
+}
+
+fn write_bundle(file: File) -> zip::result::ZipResult<()> {
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    let home = dirs::home_dir().map(|h| h.display().to_string());
+    let home = home.as_deref();
+
+    zip.start_file("summary.txt", options)?;
+    zip.write_all(redact_home(&summary(), home).as_bytes())?;
+
+    if let Ok(config) = fs::read_to_string(get_spear_config_path()) {
+        zip.start_file("config.toml", options)?;
+        zip.write_all(redact_config(&config, home).as_bytes())?;
+    }
+    let peacock_dir = SPEAR_PATH.join("peacock");
+    for (name, source) in [
+        ("options.ini", get_peacock_config_path()),
+        ("version.txt", peacock_dir.join("version.txt")),
+        ("repo.txt", peacock_dir.join("repo.txt")),
+        ("server.toml", peacock_dir.join("server.toml")),
+    ] {
+        if let Ok(content) = fs::read(&source) {
+            zip.start_file(name, options)?;
+            zip.write_all(redact_home(&String::from_utf8_lossy(&content), home).as_bytes())?;
+        }
+    }
+
+    let logs_dir = get_logs_dir();
+    let crash_reports = fs::read_dir(get_crash_reports_dir())
+        .map(|entries| {
+            let mut paths: Vec<PathBuf> =
+                entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            paths.sort();
+            paths
+        })
+        .unwrap_or_default();
+    let files = ["launcher", "hitman3", "peacock"]
+        .iter()
+        .flat_map(|prefix| newest(list_logs(&logs_dir, prefix), LOGS_PER_KIND))
+        .map(|p| ("logs", p))
+        .chain(
+            newest(crash_reports, CRASH_REPORTS)
+                .into_iter()
+                .map(|p| ("crashes", p)),
+        );
+    for (folder, file) in files {
+        let (Some(name), Ok(content)) = (file.file_name(), fs::read(&file)) else {
+            continue;
+        };
+        zip.start_file(format!("{}/{}", folder, name.to_string_lossy()), options)?;
+        zip.write_all(redact_home(&String::from_utf8_lossy(&content), home).as_bytes())?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Builds the bundle on a background thread, reporting to [`DIAGNOSTICS_STATUS`].
+pub fn spawn_diagnostics_bundle() {
+    *DIAGNOSTICS_STATUS.lock().unwrap() = Some("Creating diagnostics bundle...".to_string());
+    std::thread::spawn(|| {
+        let status = match create_diagnostics_bundle() {
+            Ok(path) => format!("Diagnostics bundle saved to {}", path.display()),
+            Err(e) => {
+                log::error!("[!] {}", e);
+                e.to_string()
+            }
+        };
+        *DIAGNOSTICS_STATUS.lock().unwrap() = Some(status);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_values_are_redacted() {
+        let config =
+            "peacock_github_repo = \"a/b\"\ngithub_token = \"ghp_123\"\n# api_key = example\n";
+        let redacted = redact_config(config, None);
+        assert!(redacted.contains("peacock_github_repo = \"a/b\""));
+        assert!(redacted.contains("github_token = \"<redacted>\""));
+        assert!(!redacted.contains("ghp_123"));
+        assert!(redacted.contains("# api_key = example"));
+    }
+
+    #[test]
+    fn only_whole_secret_words_are_redacted() {
+        let config = "api_key = \"abc\"\nkeyboard_layout = \"qwerty\"\nmonkey_mode = true\n";
+        let redacted = redact_config(config, None);
+        assert!(redacted.contains("api_key = \"<redacted>\""));
+        assert!(redacted.contains("keyboard_layout = \"qwerty\""));
+        assert!(redacted.contains("monkey_mode = true"));
+    }
+
+    #[test]
+    fn home_folder_is_hidden() {
+        let config = "data_dir = \"C:\\\\Users\\\\alice\\\\spear\"\n";
+        let redacted = redact_config(config, Some("C:\\Users\\alice"));
+        assert_eq!(redacted, "data_dir = \"%USERPROFILE%\\\\spear\"\n");
+    }
+
+    #[test]
+    fn home_folder_is_hidden_in_logs() {
+        let log = "[INFO] Loaded layout from \"C:\\Users\\alice\\spear\\layout.toml\"\n";
+        assert_eq!(
+            redact_home(log, Some("C:\\Users\\alice")),
+            "[INFO] Loaded layout from \"%USERPROFILE%\\spear\\layout.toml\"\n"
+        );
+        assert_eq!(redact_home(log, None), log);
+    }
+
+    #[test]
+    fn newest_keeps_the_last_entries() {
+        let paths = vec![
+            PathBuf::from("a.log"),
+            PathBuf::from("b.log"),
+            PathBuf::from("c.log"),
+        ];
+        assert_eq!(
+            newest(paths, 2),
+            vec![PathBuf::from("c.log"), PathBuf::from("b.log")]
+        );
+    }
+}
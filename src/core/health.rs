@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Peacock listens on the default HTTP port.
+pub const PEACOCK_PORT: u16 = 80;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    /// Started, not answering yet.
+    Starting,
+    Ready,
+    /// Never answered within the startup timeout.
+    TimedOut,
+    /// Answered before but stopped answering.
+    NotResponding,
+}
+
+impl Health {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Health::Starting => "starting",
+            Health::Ready => "ready",
+            Health::TimedOut => "not answering after startup",
+            Health::NotResponding => "not responding",
+        }
+    }
+}
+
+pub fn local_addr(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+/// Fails with `AddrInUse` when another program already listens on `port`.
+pub fn check_port_free(port: u16) -> io::Result<()> {
+    match TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("port {} is already in use by another program", port),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sends a plain `GET /` and returns the HTTP status code. Any status counts
+/// as the server answering; only connection and protocol errors are failures.
+pub fn probe(addr: SocketAddr, timeout: Duration) -> io::Result<u16> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+    let mut head = [0u8; 32];
+    let mut len = 0;
+    while len < head.len() {
+        match stream.read(&mut head[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let head = String::from_utf8_lossy(&head[..len]);
+    let mut parts = head.split_whitespace();
+    match (parts.next(), parts.next().and_then(|s| s.parse().ok())) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => Ok(status),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "response is not HTTP",
+        )),
+    }
+}
+
+/// Turns a stream of probe results into health changes.
+pub struct HealthTracker {
+    pub health: Health,
+    started: Instant,
+    startup_timeout: Duration,
+    failures_allowed: u32,
+    failures: u32,
+}
+
+impl HealthTracker {
+    pub fn new(now: Instant, startup_timeout: Duration, failures_allowed: u32) -> Self {
+        Self {
+            health: Health::Starting,
+            started: now,
+            startup_timeout,
+            failures_allowed,
+            failures: 0,
+        }
+    }
+
+    /// Returns the new health when this probe changed it.
+    pub fn observe(&mut self, now: Instant, answered: bool) -> Option<Health> {
+        let next = if answered {
+            self.failures = 0;
+            Health::Ready
+        } else {
+            self.failures += 1;
+            match self.health {
+                Health::Starting if now.duration_since(self.started) >= self.startup_timeout => {
+                    Health::TimedOut
+                }
+                Health::Ready if self.failures > self.failures_allowed => Health::NotResponding,
+                health => health,
+            }
+        };
+        if next == self.health {
+            return None;
+        }
+        self.health = next;
+        Some(next)
+    }
+}
+
+/// Probes `addr` every `interval` and sends `(pid, health)` on every change
+/// until `alive` returns false.
+pub fn monitor(
+    pid: u32,
+    addr: SocketAddr,
+    interval: Duration,
+    mut tracker: HealthTracker,
+    status: Sender<(u32, Health)>,
+    alive: impl Fn() -> bool,
+) {
+    let _ = status.send((pid, tracker.health));
+    while alive() {
+        let answered = probe(addr, interval).is_ok();
+        if let Some(health) = tracker.observe(Instant::now(), answered)
+            && status.send((pid, health)).is_err()
+        {
+            break;
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stand-in for the Peacock server answering every request with `status`.
+    fn serve(status: u16) -> SocketAddr {
+        let listener = TcpListener::bind(local_addr(0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut request = [0u8; 512];
+                let _ = stream.read(&mut request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} OK\r\nContent-Length: 0\r\n\r\n",
+                    status
+                );
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn probe_reads_the_status_code() {
+        let addr = serve(404);
+        assert_eq!(probe(addr, Duration::from_secs(2)).unwrap(), 404);
+    }
+
+    #[test]
+    fn probe_fails_without_a_server() {
+        let addr = TcpListener::bind(local_addr(0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(probe(addr, Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn port_in_use_is_detected() {
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let err = check_port_free(port).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        drop(listener);
+        assert!(check_port_free(port).is_ok());
+    }
+
+    #[test]
+    fn tracker_reports_startup_and_liveness() {
+        let t0 = Instant::now();
+        let mut tracker = HealthTracker::new(t0, Duration::from_secs(10), 1);
+        assert_eq!(tracker.observe(t0 + Duration::from_secs(1), false), None);
+        assert_eq!(
+            tracker.observe(t0 + Duration::from_secs(2), true),
+            Some(Health::Ready)
+        );
+        // One missed probe is tolerated, the second is not.
+        assert_eq!(tracker.observe(t0 + Duration::from_secs(3), false), None);
+        assert_eq!(
+            tracker.observe(t0 + Duration::from_secs(4), false),
+            Some(Health::NotResponding)
+        );
+        assert_eq!(
+            tracker.observe(t0 + Duration::from_secs(5), true),
+            Some(Health::Ready)
+        );
+    }
+
+    #[test]
+    fn tracker_times_out_and_recovers_late() {
+        let t0 = Instant::now();
+        let mut tracker = HealthTracker::new(t0, Duration::from_secs(10), 1);
+        assert_eq!(
+            tracker.observe(t0 + Duration::from_secs(10), false),
+            Some(Health::TimedOut)
+        );
+        assert_eq!(
+            tracker.observe(t0 + Duration::from_secs(12), true),
+            Some(Health::Ready)
+        );
+    }
+
+    #[test]
+    fn monitor_sends_changes_over_the_channel() {
+        let addr = serve(200);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tracker = HealthTracker::new(Instant::now(), Duration::from_secs(5), 1);
+        let probes = std::cell::Cell::new(0);
+        monitor(7, addr, Duration::from_millis(10), tracker, tx, || {
+            probes.set(probes.get() + 1);
+            probes.get() <= 3
+        });
+        let events: Vec<(u32, Health)> = rx.try_iter().collect();
+        assert_eq!(events, vec![(7, Health::Starting), (7, Health::Ready)]);
+    }
+}
@@ -1,13 +1,11 @@
+use crate::error::{Context, SpearError, SpearResult};
+
 use backtrace::Backtrace;
-use chrono;
 use log;
 use simplelog::*;
 use std::ffi::CString;
 use std::fs;
 use std::mem::{size_of, zeroed};
-use std::os::windows::process::CommandExt;
-use std::panic::catch_unwind;
-use std::process::Stdio;
 
 use std::thread;
 use std::time::Duration;
@@ -25,24 +23,48 @@ use winapi::um::winnt::{
 };
 use winapi::um::winuser::{SW_HIDE, SW_SHOW, SetForegroundWindow, ShowWindow};
 
-#[cfg(debug_assertions)]
-pub fn setup_logging() {
-    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S").to_string();
-    let log_filename = format!("spear_log_{}.txt", timestamp);
+const LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const LOG_MAX_FILES: usize = 10;
+
+/// Logs to `SPEAR_PATH/logs/<host>-<timestamp>.log`, one set of files per host
+/// process (`launcher`, `hitman3`), plus the settings log viewer and the
+/// console in debug builds.
+///
+/// The loggers take every level and `log::set_max_level` does the filtering,
+/// so loading the config to find the level is itself logged.
+pub fn setup_logging(host: &str) {
     let file_logger: Box<dyn SharedLogger> = WriteLogger::new(
-        LevelFilter::Info,
+        LevelFilter::Trace,
         Config::default(),
-        std::fs::File::create(&log_filename).unwrap(),
+        crate::core::logs::CapturedLog::new(
+            crate::core::logs::RotatingLog::new(
+                crate::core::logs::get_logs_dir(),
+                host,
+                LOG_MAX_BYTES,
+                LOG_MAX_FILES,
+            ),
+            crate::core::logs::LogSource::Spear,
+        ),
     );
+    #[allow(unused_mut)]
     let mut loggers = vec![file_logger];
-    let term_logger: Box<dyn SharedLogger> = TermLogger::new(
-        LevelFilter::Info,
-        Config::default(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    );
-    loggers.push(term_logger);
-    CombinedLogger::init(loggers).unwrap();
+    #[cfg(debug_assertions)]
+    {
+        let term_logger: Box<dyn SharedLogger> = TermLogger::new(
+            LevelFilter::Trace,
+            Config::default(),
+            TerminalMode::Mixed,
+            ColorChoice::Auto,
+        );
+        loggers.push(term_logger);
+    }
+    // Only fails when a logger is already installed, which then stays in use.
+    let _ = CombinedLogger::init(loggers);
+    log::set_max_level(LevelFilter::Info);
+    let level = crate::config::runtime::resolve_runtime_settings()
+        .settings
+        .log_level;
+    log::set_max_level(level);
 
     std::panic::set_hook(Box::new(|panic_info| {
         let bt = Backtrace::new();
@@ -51,7 +73,11 @@ pub fn setup_logging() {
     }));
 }
 
-pub fn initialize_everything() {
+/// How long the launcher window stays hidden waiting for the overlay. After
+/// that it is shown as-is, so a broken overlay never hides the launcher.
+const UI_READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub fn initialize_everything() -> SpearResult<()> {
     unsafe {
         log::info!("[+] DLL injected successfully - init thread running");
 
@@ -61,14 +87,33 @@ pub fn initialize_everything() {
         crate::REAL_WINMM = winapi::um::libloaderapi::LoadLibraryA(
             b"c:\\windows\\system32\\winmm.dll\0".as_ptr() as *const i8,
         );
-        log::info!(
-            "[+] Loaded real winmm.dll @ 0x{:x}",
-            crate::REAL_WINMM as usize
-        );
+        if crate::REAL_WINMM.is_null() {
+            log::error!(
+                "[!] Failed to load real winmm.dll: {}",
+                std::io::Error::last_os_error()
+            );
+        } else {
+            log::info!(
+                "[+] Loaded real winmm.dll @ 0x{:x}",
+                crate::REAL_WINMM as usize
+            );
+        }
 
-        let _ = catch_unwind(|| crate::hooks::setup_hooks());
+        let resolved = crate::config::runtime::resolve_runtime_settings();
+        for warning in &resolved.warnings {
+            log::error!("[!] {}", warning);
+        }
+        if resolved.settings.skip_overlay {
+            log::info!("[+] Overlay skipped for this launch");
+            return Ok(());
+        }
 
-        crate::core::resources::allocate_fake_resource();
+        // Without the hooks the launcher keeps its own background.
+        if let Err(e) = crate::core::resources::allocate_fake_resource()
+            .and_then(|_| crate::hooks::setup_hooks())
+        {
+            crate::error::report(&e);
+        }
 
         loop {
             if let Some(main_hwnd) = crate::gui::find_main_window() {
@@ -77,14 +122,16 @@ pub fn initialize_everything() {
                 let main_hwnd_usize = main_hwnd as usize;
                 std::thread::spawn(move || {
                     let main_hwnd = main_hwnd_usize as winapi::shared::windef::HWND;
-                    loop {
-                        if *crate::gui::overlay_ui::UI_READY.lock().unwrap() {
-                            ShowWindow(main_hwnd, SW_SHOW);
-                            SetForegroundWindow(main_hwnd);
+                    let started = std::time::Instant::now();
+                    while !*crate::gui::overlay_ui::UI_READY.lock().unwrap() {
+                        if started.elapsed() >= UI_READY_TIMEOUT {
+                            log::error!("[!] Overlay not ready, showing the launcher without it");
                             break;
                         }
                         std::thread::sleep(std::time::Duration::from_millis(100));
                     }
+                    ShowWindow(main_hwnd, SW_SHOW);
+                    SetForegroundWindow(main_hwnd);
                 });
                 log::info!("[+] Overlay thread spawned");
                 break;
@@ -97,20 +144,23 @@ pub fn initialize_everything() {
         crate::gui::overlay_thread::overlay_thread_func(std::ptr::null_mut());
     }
     log::info!("[+] Initialization complete");
+    Ok(())
 }
 
-pub fn spawn_watchdog() {
+pub fn spawn_watchdog() -> SpearResult<()> {
     log::info!("[+] Spawning watchdog");
     let spear_path = crate::constants::SPEAR_PATH.clone();
     if !spear_path.exists() {
-        fs::create_dir_all(&spear_path).unwrap();
+        fs::create_dir_all(&spear_path).context(format!(
+            "Failed to create the data folder {}",
+            spear_path.display()
+        ))?;
     }
 
     log::info!("[+] Creating job object");
     let job_handle = unsafe { CreateJobObjectW(std::ptr::null_mut(), std::ptr::null()) };
     if job_handle.is_null() {
-        log::error!("[!] Failed to create job object");
-        return;
+        return Err(std::io::Error::last_os_error()).context("Failed to create job object");
     }
     log::info!("[+] Job object created successfully");
 
@@ -127,33 +177,12 @@ pub fn spawn_watchdog() {
     }
     log::info!("[+] Job object configured to kill on close");
 
-    // Start node server
-    log::info!("[+] Starting node server");
-    let node_path = spear_path.join("peacock").join("nodedist").join("node.exe");
-    let server = std::process::Command::new(&node_path)
-        .arg("chunk0.js")
-        .current_dir(&spear_path.join("peacock"))
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .creation_flags(0x08000000)
-        .spawn()
-        .expect("Failed to start server");
-    log::info!("[+] Node server started with PID {}", server.id());
-
-    let server_pid = server.id();
-    let server_handle = unsafe { OpenProcess(PROCESS_ALL_ACCESS, FALSE, server_pid) };
-    if server_handle != 0 as HANDLE {
-        let assign_result = unsafe { AssignProcessToJobObject(job_handle, server_handle) };
-        if assign_result != 0 {
-            log::info!("[+] Server assigned to job object");
-        } else {
-            log::error!("[!] Failed to assign server to job object");
-        }
-        unsafe { CloseHandle(server_handle) };
-    } else {
-        log::error!("[!] Failed to open server process handle");
-    }
+    crate::config::profiles::apply_active_profile();
+
+    let job_handle_usize = job_handle as usize;
+    thread::spawn(move || {
+        crate::core::peacock_server::supervise_peacock_server(job_handle_usize as HANDLE);
+    });
 
     log::info!("[+] Starting PeacockPatcher");
     let patcher_path = spear_path.join("peacock").join("PeacockPatcher.exe");
@@ -166,8 +195,9 @@ pub fn spawn_watchdog() {
     si.hStdError = INVALID_HANDLE_VALUE;
     let mut pi: PROCESS_INFORMATION = unsafe { zeroed() };
     let application_name = std::ptr::null::<i8>();
-    let command_line = CString::new(format!("\"{}\"", patcher_path.display())).unwrap();
-    unsafe {
+    let command_line = CString::new(format!("\"{}\"", patcher_path.display()))
+        .map_err(|e| SpearError::format("Invalid PeacockPatcher path", e))?;
+    let created = unsafe {
         CreateProcessA(
             application_name,
             command_line.as_ptr() as *mut i8,
@@ -179,17 +209,27 @@ pub fn spawn_watchdog() {
             std::ptr::null(),
             &mut si,
             &mut pi,
-        );
-        CloseHandle(pi.hThread);
-        let assign_result = AssignProcessToJobObject(job_handle, pi.hProcess);
-        if assign_result != 0 {
-            log::info!("[+] PeacockPatcher assigned to job object");
-        } else {
-            log::error!("[!] Failed to assign PeacockPatcher to job object");
+        )
+    };
+    // The game still runs without the patcher, it just can't reach Peacock.
+    if created == 0 {
+        crate::error::report(&SpearError::io(
+            "Failed to start PeacockPatcher",
+            std::io::Error::last_os_error(),
+        ));
+    } else {
+        unsafe {
+            CloseHandle(pi.hThread);
+            let assign_result = AssignProcessToJobObject(job_handle, pi.hProcess);
+            if assign_result != 0 {
+                log::info!("[+] PeacockPatcher assigned to job object");
+            } else {
+                log::error!("[!] Failed to assign PeacockPatcher to job object");
+            }
+            CloseHandle(pi.hProcess);
         }
-        CloseHandle(pi.hProcess);
+        log::info!("[+] PeacockPatcher started");
     }
-    log::info!("[+] PeacockPatcher started");
 
     log::info!("[+] Waiting to assign HITMAN3.exe to job object");
     let mut hitman_assigned = false;
@@ -223,4 +263,5 @@ pub fn spawn_watchdog() {
     }
 
     log::info!("[+] Watchdog setup complete - job object will manage process lifecycle");
+    Ok(())
 }
@@ -3,13 +3,22 @@ use dll_syringe::{Syringe, process::OwnedProcess};
 use num_cpus;
 use std::ptr;
 use std::slice;
+use std::time::{Duration, Instant};
 use sysinfo::System;
 use winapi::um::memoryapi::VirtualProtect;
 use winapi::um::processthreadsapi::ExitProcess;
 use winapi::um::winnt::PAGE_READWRITE;
 
+use crate::core::health::{PEACOCK_PORT, check_port_free};
+use crate::core::logs::Severity;
+use crate::error::{Context, SpearError, SpearResult};
+use crate::gui::notifications::notify;
 use crate::gui::overlay_utils::get_text_section;
 
+/// How long PLAY waits for the game process before telling the user it is
+/// still waiting. It keeps waiting, since Steam may be updating the game.
+const GAME_START_TIMEOUT: Duration = Duration::from_secs(60);
+
 // AOB Patterns
 //
 // Function for PLAY button
@@ -28,127 +37,182 @@ const REPLACEMENT_CALL: &[u8] = &[
     0x84, 0xC0, 0x74, 0x08, 0x48, 0x8B, 0xCB, 0x90, 0x90, 0x90, 0x90, 0x90,
 ];
 
-pub fn perform_injection() {
-    log::info!("[+] PLAY button clicked - disabling button and scanning for patterns");
+/// Whether one of the patterns above was found in the running launcher.
+pub struct PatternMatch {
+    pub name: &'static str,
+    pub offset: Option<usize>,
+}
 
-    if let Some((text_base, text_size)) = get_text_section() {
-        let text_data = unsafe { slice::from_raw_parts(text_base, text_size) };
-        log::info!(
-            "[+] Scanning .text section (base: {:p}, size: {})",
-            text_base,
-            text_size
-        );
+fn find_pattern(text_data: &[u8], bytes: &[u8]) -> Option<usize> {
+    let pattern = Pattern::new(bytes.to_vec(), vec![true; bytes.len()], num_cpus::get());
+    let mut found = None;
+    pattern.scan(text_data, |offset| {
+        found = Some(offset);
+        false
+    });
+    found
+}
 
-        let pattern1 = Pattern::new(
-            PATTERN_FUN_140014684.to_vec(),
-            vec![true; PATTERN_FUN_140014684.len()],
-            num_cpus::get(),
-        );
-        let mut found1 = None;
-        pattern1.scan(text_data, |offset| {
-            found1 = Some(offset);
-            false
-        });
-        if let Some(offset) = found1 {
-            let addr = unsafe { text_base.add(offset) };
-            log::info!(
-                "[+] Found FUN_140014684 at offset {} (address: {:p})",
-                offset,
-                addr
-            );
-        } else {
-            log::error!("[!] Pattern for FUN_140014684 not found");
+/// Scans the launcher's .text section without patching anything, for diagnostics.
+pub fn scan_patterns() -> Option<Vec<PatternMatch>> {
+    let (text_base, text_size) = get_text_section()?;
+    let text_data = unsafe { slice::from_raw_parts(text_base, text_size) };
+    Some(vec![
+        PatternMatch {
+            name: "FUN_140014684",
+            offset: find_pattern(text_data, PATTERN_FUN_140014684),
+        },
+        PatternMatch {
+            name: "CALL FUN_1400090ec",
+            offset: find_pattern(text_data, PATTERN_CALL)
+                .or_else(|| find_pattern(text_data, REPLACEMENT_CALL)),
+        },
+    ])
+}
+
+/// Patches the launcher's PLAY handler so it doesn't close the launcher, runs
+/// it and injects spear into the game once it starts. Exits the launcher on
+/// success; on failure the launcher is left as it was, ready to retry.
+pub fn perform_injection() -> SpearResult<()> {
+    log::info!("[+] PLAY button clicked - scanning for patterns");
+
+    let (text_base, text_size) = get_text_section().context("Failed to get .text section")?;
+    let text_data = unsafe { slice::from_raw_parts(text_base, text_size) };
+    log::info!(
+        "[+] Scanning .text section (base: {:p}, size: {})",
+        text_base,
+        text_size
+    );
+
+    let play_offset = find_pattern(text_data, PATTERN_FUN_140014684)
+        .context("Pattern for FUN_140014684 not found, this launcher version is not supported")?;
+    let func_addr = unsafe { text_base.add(play_offset) };
+    log::info!(
+        "[+] Found FUN_140014684 at offset {} (address: {:p})",
+        play_offset,
+        func_addr
+    );
+
+    let call_offset = find_pattern(text_data, PATTERN_CALL).context(
+        "Pattern for CALL FUN_1400090ec not found, this launcher version is not supported",
+    )?;
+    let call_addr = unsafe { text_base.add(call_offset) };
+    log::info!(
+        "[+] Found CALL FUN_1400090ec at offset {} (address: {:p}) - applying NOP patch",
+        call_offset,
+        call_addr
+    );
+    write_code(call_addr, REPLACEMENT_CALL)?;
+    log::info!("[+] NOP patch applied successfully");
+
+    let result = launch_and_inject(func_addr);
+    if result.is_err() {
+        // Lets the launcher's own PLAY button close it again.
+        match write_code(call_addr, PATTERN_CALL) {
+            Ok(()) => log::info!("[+] Restored CALL FUN_1400090ec"),
+            Err(e) => log::error!("[!] {}", e),
         }
+    }
+    result
+}
+
+/// Overwrites the launcher's code at `addr` with `bytes`.
+fn write_code(addr: *const u8, bytes: &[u8]) -> SpearResult<()> {
+    let mut old_protect: u32 = 0;
+    if unsafe {
+        VirtualProtect(
+            addr as *mut _,
+            bytes.len(),
+            PAGE_READWRITE,
+            &mut old_protect,
+        )
+    } == 0
+    {
+        return Err(SpearError::io(
+            "Failed to change memory protection for patching",
+            std::io::Error::last_os_error(),
+        ));
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), addr as *mut u8, bytes.len());
+        VirtualProtect(addr as *mut _, bytes.len(), old_protect, &mut old_protect);
+    }
+    Ok(())
+}
 
-        let pattern2 = Pattern::new(
-            PATTERN_CALL.to_vec(),
-            vec![true; PATTERN_CALL.len()],
-            num_cpus::get(),
+/// Runs the PLAY handler at `func_addr`, waits for the game and injects spear.
+fn launch_and_inject(func_addr: *const u8) -> SpearResult<()> {
+    if let Err(e) = check_port_free(PEACOCK_PORT) {
+        log::error!("[!] {}", e);
+        notify(
+            Severity::Warn,
+            "Peacock may not be able to start",
+            Some(e.to_string()),
         );
-        let mut found2 = None;
-        pattern2.scan(text_data, |offset| {
-            found2 = Some(offset);
-            false
-        });
-        if let Some(offset) = found2 {
-            let addr = unsafe { text_base.add(offset) };
-            log::info!(
-                "[+] Found CALL FUN_1400090ec at offset {} (address: {:p}) - applying NOP patch",
-                offset,
-                addr
-            );
+    }
+
+    let func: extern "C" fn() = unsafe { std::mem::transmute(func_addr) };
+    log::info!("[+] Calling FUN_140014684 at {:p}", func_addr);
+    func();
 
-            let mut old_protect: u32 = 0;
-            if unsafe {
-                VirtualProtect(
-                    addr as *mut _,
-                    REPLACEMENT_CALL.len(),
-                    PAGE_READWRITE,
-                    &mut old_protect,
-                )
-            } != 0
-            {
-                unsafe {
-                    ptr::copy_nonoverlapping(
-                        REPLACEMENT_CALL.as_ptr(),
-                        addr as *mut u8,
-                        REPLACEMENT_CALL.len(),
-                    )
-                };
-                log::info!("[+] NOP patch applied successfully");
-
-                unsafe {
-                    VirtualProtect(
-                        addr as *mut _,
-                        REPLACEMENT_CALL.len(),
-                        old_protect,
-                        &mut old_protect,
-                    )
-                };
-
-                if let Some(offset) = found1 {
-                    let func_addr = unsafe { text_base.add(offset) };
-                    let func: extern "C" fn() = unsafe { std::mem::transmute(func_addr) };
-                    log::info!("[+] Calling FUN_140014684 at {:p}", func_addr);
-                    func();
-
-                    log::info!("[+] Waiting for HITMAN3.exe to start...");
-                    let mut system = System::new_all();
-                    loop {
-                        system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
-                        if system
-                            .processes()
-                            .values()
-                            .any(|p| p.name() == "HITMAN3.exe")
-                        {
-                            log::info!("[+] HITMAN3.exe found in process list");
-                            break;
-                        }
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
-
-                    log::info!("[+] Injecting winmm.dll into HITMAN3.exe");
-                    if let Some(owned_process) = OwnedProcess::find_first_by_name("HITMAN3.exe") {
-                        match Syringe::for_process(owned_process).inject("winmm.dll") {
-                            Ok(_) => {
-                                log::info!("[+] Successfully injected winmm.dll");
-                                unsafe {
-                                    ExitProcess(0);
-                                }
-                            }
-                            Err(e) => log::error!("[!] Failed to inject winmm.dll: {:?}", e),
-                        }
-                    } else {
-                        log::error!("[!] Failed to find HITMAN3.exe process for injection");
-                    }
-                }
-            } else {
-                log::error!("[!] Failed to change memory protection for patching");
-            }
-        } else {
-            log::error!("[!] Pattern for CALL FUN_1400090ec not found");
+    // The launch can't be taken back, so a second PLAY would start a second
+    // game: wait for this one however long it takes.
+    log::info!("[+] Waiting for HITMAN3.exe to start...");
+    let started = Instant::now();
+    let mut warned = false;
+    let mut system = System::new_all();
+    while !is_game_running(&mut system) {
+        if !warned && started.elapsed() >= GAME_START_TIMEOUT {
+            warned = true;
+            log::error!(
+                "[!] HITMAN3.exe did not start within {} seconds, still waiting",
+                GAME_START_TIMEOUT.as_secs()
+            );
+            notify(
+                Severity::Warn,
+                "HITMAN3 is taking a while to start",
+                Some(
+                    "spear keeps waiting for it. If the game never opens, close the launcher \
+                     and start it again."
+                        .to_string(),
+                ),
+            );
         }
-    } else {
-        log::error!("[!] Failed to get .text section");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    log::info!("[+] HITMAN3.exe found in process list");
+
+    log::info!("[+] Injecting winmm.dll into HITMAN3.exe");
+    let owned_process = OwnedProcess::find_first_by_name("HITMAN3.exe")
+        .context("Failed to find HITMAN3.exe process for injection")?;
+    Syringe::for_process(owned_process)
+        .inject("winmm.dll")
+        .map_err(|e| {
+            SpearError::hook(
+                "Failed to inject winmm.dll into HITMAN3.exe (close the game and press PLAY again)",
+                format!("{:?}", e),
+            )
+        })?;
+    log::info!("[+] Successfully injected winmm.dll");
+    unsafe {
+        ExitProcess(0);
+    }
+    Ok(())
+}
+
+fn is_game_running(system: &mut System) -> bool {
+    system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    system
+        .processes()
+        .values()
+        .any(|p| p.name() == "HITMAN3.exe")
+}
+
+/// Blocks until no HITMAN3.exe is running, so a failed launch can't be
+/// followed by a second game.
+pub fn wait_for_game_exit() {
+    let mut system = System::new_all();
+    while is_game_running(&mut system) {
+        std::thread::sleep(Duration::from_millis(500));
     }
 }
@@ -0,0 +1,209 @@
+use crate::constants::SPEAR_PATH;
+use crate::core::logs::Severity;
+use crate::error::Context;
+use crate::gui::notifications::{notify, notify_error};
+
+use lazy_static::lazy_static;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstallState {
+    /// Looking at what is installed.
+    Checking,
+    /// Fraction of the release downloaded so far, 0.0 to 1.0.
+    Downloading(f32),
+    Extracting,
+    Ready,
+    Failed(String),
+}
+
+impl InstallState {
+    pub fn label(&self) -> String {
+        match self {
+            InstallState::Checking => "Checking Peacock...".to_string(),
+            InstallState::Downloading(fraction) => {
+                format!("Downloading Peacock {}%", (fraction * 100.0).floor() as u32)
+            }
+            InstallState::Extracting => "Extracting Peacock...".to_string(),
+            InstallState::Ready => "Peacock ready".to_string(),
+            InstallState::Failed(_) => "Peacock download failed, click to retry".to_string(),
+        }
+    }
+
+    /// How full the progress bar is, `None` when there is nothing to show.
+    pub fn progress(&self) -> Option<f32> {
+        match self {
+            InstallState::Checking => Some(0.0),
+            InstallState::Downloading(fraction) => Some(fraction.clamp(0.0, 1.0)),
+            InstallState::Extracting | InstallState::Failed(_) => Some(1.0),
+            InstallState::Ready => None,
+        }
+    }
+}
+
+lazy_static! {
+    /// Starts busy so PLAY stays disabled until the first check is done.
+    pub static ref INSTALL_STATE: Mutex<InstallState> = Mutex::new(InstallState::Checking);
+    static ref INSTALL_RUNNING: Mutex<bool> = Mutex::new(false);
+}
+
+pub fn install_state() -> InstallState {
+    INSTALL_STATE.lock().unwrap().clone()
+}
+
+pub fn set_install_state(state: InstallState) {
+    let mut current = INSTALL_STATE.lock().unwrap();
+    if std::mem::discriminant(&*current) != std::mem::discriminant(&state) {
+        log::info!("[+] Peacock install: {}", state.label());
+    }
+    *current = state;
+}
+
+/// Bytes received across all assets of a release.
+pub struct DownloadProgress {
+    total: u64,
+    received: u64,
+}
+
+impl DownloadProgress {
+    pub fn new(total: u64) -> Self {
+        Self { total, received: 0 }
+    }
+
+    /// Counts `bytes` more and returns the fraction done.
+    pub fn add(&mut self, bytes: u64) -> f32 {
+        self.received += bytes;
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.received as f64 / self.total as f64).min(1.0) as f32
+    }
+}
+
+/// Whether there is a server to start, even if it isn't the latest one.
+pub fn has_usable_install(peacock_dir: &Path) -> bool {
+    peacock_dir.join("chunk0.js").exists()
+}
+
+pub fn needs_download(peacock_dir: &Path, force_reinstall: bool) -> bool {
+    force_reinstall || !has_usable_install(peacock_dir)
+}
+
+fn installed_version(peacock_dir: &Path) -> String {
+    std::fs::read_to_string(peacock_dir.join("version.txt"))
+        .map(|v| v.trim().to_string())
+        .unwrap_or("unknown".to_string())
+}
+
+/// Downloads Peacock on a background thread when it is missing or a
+/// reinstall was asked for, publishing progress in [`INSTALL_STATE`].
+pub fn spawn_install(force_reinstall: bool) {
+    {
+        let mut running = INSTALL_RUNNING.lock().unwrap();
+        if *running {
+            return;
+        }
+        *running = true;
+    }
+    std::thread::spawn(move || {
+        run_install(force_reinstall);
+        *INSTALL_RUNNING.lock().unwrap() = false;
+    });
+}
+
+fn run_install(force_reinstall: bool) {
+    set_install_state(InstallState::Checking);
+    let peacock_dir = SPEAR_PATH.join("peacock");
+    let version = if needs_download(&peacock_dir, force_reinstall) {
+        if force_reinstall {
+            log::info!("[+] Forcing Peacock reinstall");
+        }
+        crate::gui::overlay_ui::set_peacock_version(None);
+        let result = tokio::runtime::Runtime::new()
+            .context("Failed to start the download runtime")
+            .and_then(|rt| {
+                rt.block_on(crate::core::resources::peacock_download_release(
+                    set_install_state,
+                ))
+            });
+        match result {
+            Ok(version) => version,
+            // A reinstall or retry that fails still leaves the old server.
+            Err(e) if has_usable_install(&peacock_dir) => {
+                log::error!("[!] {}", e);
+                notify(
+                    Severity::Warn,
+                    "Failed to update Peacock, using the installed version",
+                    Some(e.to_string()),
+                );
+                installed_version(&peacock_dir)
+            }
+            Err(e) => {
+                log::error!("[!] {}", e);
+                notify_error("Failed to download Peacock", &e);
+                set_install_state(InstallState::Failed(e.to_string()));
+                return;
+            }
+        }
+    } else {
+        installed_version(&peacock_dir)
+    };
+    crate::gui::overlay_ui::set_peacock_version(Some(version.trim_start_matches('v').to_string()));
+
+    let problems = crate::core::resources::check_installation(&peacock_dir);
+    if !problems.is_empty() {
+        log::error!("[!] Peacock install is incomplete: {}", problems.join(", "));
+        notify(
+            Severity::Warn,
+            "Peacock install is incomplete, the game may not start",
+            Some(problems.join("\n")),
+        );
+    }
+
+    crate::config::flag_changes::detect_flag_changes();
+    set_install_state(InstallState::Ready);
+}
+
+/// PLAY only works once Peacock is installed. A failed update falls back to
+/// the installed version, so `Failed` means there is nothing to start.
+pub fn play_allowed() -> bool {
+    *INSTALL_STATE.lock().unwrap() == InstallState::Ready
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    #[test]
+    fn progress_spans_all_assets() {
+        let mut progress = DownloadProgress::new(400);
+        assert_eq!(progress.add(100), 0.25);
+        assert_eq!(progress.add(300), 1.0);
+        // Servers may send more than the advertised size.
+        assert_eq!(progress.add(50), 1.0);
+        assert_eq!(DownloadProgress::new(0).add(10), 0.0);
+    }
+
+    #[test]
+    fn states_map_to_labels_and_bar() {
+        assert_eq!(
+            InstallState::Downloading(0.426).label(),
+            "Downloading Peacock 42%"
+        );
+        assert_eq!(InstallState::Downloading(1.5).progress(), Some(1.0));
+        assert_eq!(InstallState::Ready.progress(), None);
+    }
+
+    #[test]
+    fn download_is_needed_without_chunk0() {
+        let dir = TempDir::new("install");
+        assert!(needs_download(&dir, false));
+        assert!(!has_usable_install(&dir));
+        std::fs::write(dir.join("chunk0.js"), "x").unwrap();
+        assert!(has_usable_install(&dir));
+        assert!(!needs_download(&dir, false));
+        assert!(needs_download(&dir, true));
+    }
+}
@@ -0,0 +1,511 @@
+use crate::constants::SPEAR_PATH;
+
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LOG_BUFFER_LINES: usize = 5000;
+/// How far back a tail starts when it first opens a log.
+const TAIL_START_BYTES: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Debug => "DEBUG",
+            Severity::Info => "INFO",
+            Severity::Warn => "WARN",
+            Severity::Error => "ERROR",
+        }
+    }
+
+    fn from_word(word: &str) -> Option<Self> {
+        match word.trim().to_lowercase().as_str() {
+            "debug" | "trace" | "silly" | "verbose" => Some(Severity::Debug),
+            "info" | "information" => Some(Severity::Info),
+            "warn" | "warning" => Some(Severity::Warn),
+            "error" | "err" | "fatal" | "critical" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+/// How warnings are highlighted in the overlay and the settings window.
+pub const WARN_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 180, 60);
+/// How errors are highlighted in the overlay and the settings window.
+pub const ERROR_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 80, 80);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogSource {
+    /// This process.
+    Spear,
+    /// spear inside HITMAN3.exe, read from its log file.
+    Game,
+    Peacock,
+}
+
+impl LogSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogSource::Spear => "spear",
+            LogSource::Game => "game",
+            LogSource::Peacock => "peacock",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    pub source: LogSource,
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// The newest log lines in memory, dropping the oldest once full.
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, source: LogSource, text: &str) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(LogLine {
+            source,
+            severity: parse_severity(text, Severity::Info),
+            text: text.to_string(),
+        });
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &LogLine> {
+        self.lines.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// The last `limit` lines from `source` at or above `min`, newest last.
+    pub fn recent(&self, source: LogSource, min: Severity, limit: usize) -> Vec<String> {
+        let mut recent: Vec<String> = self
+            .lines
+            .iter()
+            .rev()
+            .filter(|line| line.source == source && line.severity >= min)
+            .take(limit)
+            .map(|line| line.text.clone())
+            .collect();
+        recent.reverse();
+        recent
+    }
+}
+
+lazy_static! {
+    /// Feeds the log viewer in the settings window. Never log while holding it.
+    pub static ref LOG_BUFFER: Mutex<LogBuffer> = Mutex::new(LogBuffer::new(LOG_BUFFER_LINES));
+}
+
+pub fn get_logs_dir() -> PathBuf {
+    SPEAR_PATH.join("logs")
+}
+
+/// Severity of a line of server output. Peacock tags lines like
+/// `[12:00:00:000] [warn] ...`; untagged lines fall back to `Error:`/`Warning:`
+/// prefixes, then to `default` (stdout and stderr differ).
+pub fn parse_severity(line: &str, default: Severity) -> Severity {
+    let mut rest = line;
+    while let Some(start) = rest.find('[') {
+        let Some(len) = rest[start..].find(']') else {
+            break;
+        };
+        if let Some(severity) = Severity::from_word(&rest[start + 1..start + len]) {
+            return severity;
+        }
+        rest = &rest[start + len..];
+    }
+    line.split_once(':')
+        .and_then(|(word, _)| Severity::from_word(word))
+        .unwrap_or(default)
+}
+
+/// Appends lines to `<prefix>-<timestamp>.log` files in `dir`, starting a new
+/// file once `max_bytes` is reached and keeping only the newest `max_files`.
+pub struct RotatingLog {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<File>,
+    written: u64,
+    at_line_start: bool,
+    pub path: Option<PathBuf>,
+}
+
+impl RotatingLog {
+    pub fn new(dir: PathBuf, prefix: &str, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            dir,
+            prefix: prefix.to_string(),
+            max_bytes,
+            max_files,
+            file: None,
+            written: 0,
+            at_line_start: true,
+            path: None,
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.write_all(format!("{}\n", line).as_bytes())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S_%3f").to_string();
+        let mut path = self.dir.join(format!("{}-{}.log", self.prefix, timestamp));
+        // Names have to keep sorting in creation order for pruning, even when
+        // several files are started within the same millisecond.
+        let mut n = 1;
+        while path.exists() || self.path.as_ref().is_some_and(|last| path <= *last) {
+            path = self
+                .dir
+                .join(format!("{}-{}_{:03}.log", self.prefix, timestamp, n));
+            n += 1;
+        }
+        self.file = Some(File::create(&path)?);
+        self.path = Some(path);
+        self.written = 0;
+        prune_logs(&self.dir, &self.prefix, self.max_files);
+        Ok(())
+    }
+}
+
+/// Only rotates between lines, so a record written in several pieces stays in
+/// one file.
+impl Write for RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() || (self.at_line_start && self.written >= self.max_bytes) {
+            self.rotate()?;
+        }
+        let Some(file) = &mut self.file else {
+            return Ok(0);
+        };
+        let n = file.write(buf)?;
+        self.written += n as u64;
+        if n > 0 {
+            self.at_line_start = buf[n - 1] == b'\n';
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes through to `inner` and copies every complete line into [`LOG_BUFFER`].
+pub struct CapturedLog<W> {
+    inner: W,
+    source: LogSource,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> CapturedLog<W> {
+    pub fn new(inner: W, source: LogSource) -> Self {
+        Self {
+            inner,
+            source,
+            pending: vec![],
+        }
+    }
+}
+
+impl<W: Write> Write for CapturedLog<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Capture even when the file can't be written, then report why.
+        let result = self.inner.write(buf);
+        let n = *result.as_ref().unwrap_or(&buf.len());
+        self.pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            LOG_BUFFER
+                .lock()
+                .unwrap()
+                .push(self.source, line.trim_end());
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Follows the newest `<prefix>-*.log` in a folder, including across rotations.
+#[derive(Clone, Debug)]
+pub struct LogTail {
+    dir: PathBuf,
+    prefix: String,
+    path: Option<PathBuf>,
+    offset: u64,
+    partial: String,
+    skip_first: bool,
+}
+
+impl LogTail {
+    pub fn new(dir: PathBuf, prefix: &str) -> Self {
+        Self {
+            dir,
+            prefix: prefix.to_string(),
+            path: None,
+            offset: 0,
+            partial: String::new(),
+            skip_first: false,
+        }
+    }
+
+    /// Complete lines written since the last call. The first call only reads
+    /// the last part of the file; a newer file is read from its start.
+    pub fn poll(&mut self) -> Vec<String> {
+        let Some(latest) = list_logs(&self.dir, &self.prefix).pop() else {
+            return vec![];
+        };
+        let Ok(mut file) = File::open(&latest) else {
+            return vec![];
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if self.path.as_ref() != Some(&latest) {
+            self.offset = match self.path {
+                None => len.saturating_sub(TAIL_START_BYTES),
+                Some(_) => 0,
+            };
+            self.skip_first = self.offset > 0;
+            self.partial.clear();
+            self.path = Some(latest);
+        } else if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        let mut buf = vec![];
+        if file.seek(SeekFrom::Start(self.offset)).is_err() || file.read_to_end(&mut buf).is_err() {
+            return vec![];
+        }
+        self.offset += buf.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&buf));
+        let Some(end) = self.partial.rfind('\n') else {
+            return vec![];
+        };
+        let complete: String = self.partial.drain(..=end).collect();
+        let mut lines: Vec<String> = complete.lines().map(|l| l.to_string()).collect();
+        if self.skip_first && !lines.is_empty() {
+            lines.remove(0);
+        }
+        self.skip_first = false;
+        lines
+    }
+}
+
+/// Log files in `dir` starting with `prefix`, oldest first.
+pub fn list_logs(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.extension().is_some_and(|ext| ext == "log")
+                        && p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with(&format!("{}-", prefix)))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort();
+    logs
+}
+
+fn prune_logs(dir: &Path, prefix: &str, max_files: usize) {
+    let logs = list_logs(dir, prefix);
+    if logs.len() > max_files {
+        for old in &logs[..logs.len() - max_files] {
+            if let Err(e) = fs::remove_file(old) {
+                log::error!("[!] Failed to remove old log {:?}: {}", old, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    #[test]
+    fn peacock_tags_are_parsed() {
+        assert_eq!(
+            parse_severity("[12:00:00:000] [warn] Unknown unlockable", Severity::Info),
+            Severity::Warn
+        );
+        assert_eq!(
+            parse_severity("[12:00:00:000] [ERROR] Failed to bind", Severity::Info),
+            Severity::Error
+        );
+        assert_eq!(
+            parse_severity("[12:00:00:000] [debug] [contract] loaded", Severity::Info),
+            Severity::Debug
+        );
+    }
+
+    #[test]
+    fn untagged_lines_use_prefix_or_default() {
+        assert_eq!(
+            parse_severity("Error: listen EADDRINUSE :::80", Severity::Info),
+            Severity::Error
+        );
+        assert_eq!(
+            parse_severity("    at Server.listen (node:net:1)", Severity::Warn),
+            Severity::Warn
+        );
+        assert_eq!(
+            parse_severity("Server started", Severity::Info),
+            Severity::Info
+        );
+    }
+
+    #[test]
+    fn logs_rotate_and_old_files_are_pruned() {
+        let dir = TempDir::new("logs");
+        let mut log = RotatingLog::new(dir.to_path_buf(), "peacock", 20, 2);
+        for i in 0..8 {
+            log.write_line(&format!("[info] line {}", i)).unwrap();
+        }
+        let logs = list_logs(&dir, "peacock");
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs.last(), log.path.as_ref());
+        assert!(
+            fs::read_to_string(logs.last().unwrap())
+                .unwrap()
+                .contains("line 7")
+        );
+    }
+
+    #[test]
+    fn records_written_in_pieces_are_not_split() {
+        let dir = TempDir::new("pieces");
+        let mut log = RotatingLog::new(dir.to_path_buf(), "launcher", 10, 5);
+        for _ in 0..3 {
+            write!(log, "[INFO] ").unwrap();
+            writeln!(log, "a long message").unwrap();
+        }
+        let logs = list_logs(&dir, "launcher");
+        assert_eq!(logs.len(), 3);
+        for path in logs {
+            assert_eq!(fs::read_to_string(path).unwrap(), "[INFO] a long message\n");
+        }
+    }
+
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_writes_are_captured_and_reported() {
+        let mut log = CapturedLog::new(FullDisk, LogSource::Game);
+        assert!(log.write(b"[error] disk-full-capture-test\n").is_err());
+        assert!(
+            LOG_BUFFER
+                .lock()
+                .unwrap()
+                .lines()
+                .any(|line| line.text == "[error] disk-full-capture-test")
+        );
+    }
+
+    #[test]
+    fn buffer_drops_oldest_lines() {
+        let mut buffer = LogBuffer::new(2);
+        buffer.push(LogSource::Spear, "12:00:00 [INFO] a");
+        buffer.push(LogSource::Peacock, "[warn] b");
+        buffer.push(LogSource::Peacock, "[error] c");
+        let lines: Vec<&LogLine> = buffer.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].severity, Severity::Warn);
+        assert_eq!(lines[1].text, "[error] c");
+    }
+
+    #[test]
+    fn tail_follows_appends_and_rotation() {
+        let dir = TempDir::new("tail");
+        let first = dir.join("peacock-20240101_000000_000.log");
+        fs::write(&first, "a\nb\npart").unwrap();
+        let mut tail = LogTail::new(dir.to_path_buf(), "peacock");
+        assert_eq!(tail.poll(), vec!["a", "b"]);
+        assert!(tail.poll().is_empty());
+
+        let mut file = fs::OpenOptions::new().append(true).open(&first).unwrap();
+        file.write_all(b"ial\nc\n").unwrap();
+        assert_eq!(tail.poll(), vec!["partial", "c"]);
+
+        fs::write(dir.join("peacock-20240101_000001_000.log"), "d\n").unwrap();
+        assert_eq!(tail.poll(), vec!["d"]);
+    }
+
+    #[test]
+    fn recent_lines_filter_by_source_and_severity() {
+        let mut buffer = LogBuffer::new(10);
+        for line in [
+            "[info] a",
+            "[error] b",
+            "[warn] c",
+            "[error] d",
+            "[error] e",
+        ] {
+            buffer.push(LogSource::Peacock, line);
+        }
+        buffer.push(LogSource::Game, "[error] f");
+        assert_eq!(
+            buffer.recent(LogSource::Peacock, Severity::Error, 2),
+            vec!["[error] d".to_string(), "[error] e".to_string()]
+        );
+        assert_eq!(
+            buffer.recent(LogSource::Peacock, Severity::Warn, 10).len(),
+            4
+        );
+        assert_eq!(
+            buffer.recent(LogSource::Game, Severity::Error, 10),
+            vec!["[error] f"]
+        );
+    }
+}
@@ -1,3 +1,12 @@
+pub mod diagnostics;
+pub mod health;
 pub mod init;
 pub mod injection;
+pub mod install;
+pub mod logs;
+pub mod paths;
+pub mod peacock_server;
 pub mod resources;
+pub mod supervisor;
+#[cfg(test)]
+pub mod test_support;
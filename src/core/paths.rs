@@ -0,0 +1,175 @@
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SPEAR_HOME_ENV: &str = "SPEAR_HOME";
+pub const PORTABLE_MARKER: &str = "spear.portable";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpearPathSource {
+    Env,
+    Portable,
+    Config,
+    Default,
+    Fallback,
+}
+
+impl SpearPathSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpearPathSource::Env => "SPEAR_HOME",
+            SpearPathSource::Portable => "portable mode",
+            SpearPathSource::Config => "config.toml",
+            SpearPathSource::Default => "default",
+            SpearPathSource::Fallback => "temporary fallback",
+        }
+    }
+}
+
+/// Where spear keeps its files. `config.toml` always lives in `config_dir` so
+/// it can be found before `data_dir` is known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpearPaths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub source: SpearPathSource,
+}
+
+lazy_static! {
+    pub static ref SPEAR_PATHS: SpearPaths = resolve_spear_paths();
+}
+
+/// The game folder is either the exe's directory (Launcher.exe) or its parent
+/// (Retail\HITMAN3.exe), so look for the marker in both.
+fn find_portable_dir(exe_path: &Path) -> Option<PathBuf> {
+    let exe_dir = exe_path.parent()?;
+    [Some(exe_dir), exe_dir.parent()]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.join(PORTABLE_MARKER).exists())
+        .map(|dir| dir.join("spear"))
+}
+
+/// Reads `data_dir` straight from `config.toml` without going through
+/// `load_spear_config`, which itself depends on the resolved paths.
+fn read_configured_data_dir(config_dir: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(config_dir.join("config.toml")).ok()?;
+    let table: toml::Table = toml::from_str(&content).ok()?;
+    table
+        .get("data_dir")?
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Resolution order: `SPEAR_HOME`, a `spear.portable` marker next to the game,
+/// `data_dir` from `config.toml`, then `%LOCALAPPDATA%\spear`.
+pub fn resolve_spear_paths_from(
+    env_home: Option<PathBuf>,
+    exe_path: Option<&Path>,
+    default_dir: Option<PathBuf>,
+) -> SpearPaths {
+    if let Some(home) = env_home.filter(|p| !p.as_os_str().is_empty()) {
+        return SpearPaths {
+            config_dir: home.clone(),
+            data_dir: home,
+            source: SpearPathSource::Env,
+        };
+    }
+    if let Some(portable) = exe_path.and_then(find_portable_dir) {
+        return SpearPaths {
+            config_dir: portable.clone(),
+            data_dir: portable,
+            source: SpearPathSource::Portable,
+        };
+    }
+    let (config_dir, source) = match default_dir {
+        Some(dir) => (dir, SpearPathSource::Default),
+        None => (
+            std::env::temp_dir().join("spear"),
+            SpearPathSource::Fallback,
+        ),
+    };
+    match read_configured_data_dir(&config_dir) {
+        Some(data_dir) => SpearPaths {
+            config_dir,
+            data_dir,
+            source: SpearPathSource::Config,
+        },
+        None => SpearPaths {
+            data_dir: config_dir.clone(),
+            config_dir,
+            source,
+        },
+    }
+}
+
+pub fn resolve_spear_paths() -> SpearPaths {
+    let paths = resolve_spear_paths_from(
+        std::env::var_os(SPEAR_HOME_ENV).map(PathBuf::from),
+        std::env::current_exe().ok().as_deref(),
+        dirs::data_local_dir().map(|d| d.join("spear")),
+    );
+    log::info!(
+        "[+] Spear data dir: {:?} (config dir: {:?}, from {})",
+        paths.data_dir,
+        paths.config_dir,
+        paths.source.label()
+    );
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    #[test]
+    fn env_takes_precedence() {
+        let game = TempDir::new("paths-env");
+        fs::write(game.join(PORTABLE_MARKER), "").unwrap();
+        let paths = resolve_spear_paths_from(
+            Some(PathBuf::from("D:\\spear")),
+            Some(&game.join("Launcher.exe")),
+            Some(game.join("appdata")),
+        );
+        assert_eq!(paths.source, SpearPathSource::Env);
+        assert_eq!(paths.data_dir, PathBuf::from("D:\\spear"));
+    }
+
+    #[test]
+    fn portable_marker_is_found_from_retail_subfolder() {
+        let game = TempDir::new("paths-portable");
+        fs::create_dir_all(game.join("Retail")).unwrap();
+        fs::write(game.join(PORTABLE_MARKER), "").unwrap();
+        let paths = resolve_spear_paths_from(
+            None,
+            Some(&game.join("Retail").join("HITMAN3.exe")),
+            Some(game.join("appdata")),
+        );
+        assert_eq!(paths.source, SpearPathSource::Portable);
+        assert_eq!(paths.data_dir, game.join("spear"));
+        assert_eq!(paths.config_dir, game.join("spear"));
+    }
+
+    #[test]
+    fn config_data_dir_moves_data_but_not_config() {
+        let appdata = TempDir::new("paths-config");
+        fs::write(
+            appdata.join("config.toml"),
+            "config_version = 1\ndata_dir = \"E:\\\\spear-data\"\n",
+        )
+        .unwrap();
+        let paths = resolve_spear_paths_from(None, None, Some(appdata.to_path_buf()));
+        assert_eq!(paths.source, SpearPathSource::Config);
+        assert_eq!(paths.config_dir, *appdata);
+        assert_eq!(paths.data_dir, PathBuf::from("E:\\spear-data"));
+    }
+
+    #[test]
+    fn missing_default_dir_falls_back_without_panicking() {
+        let paths = resolve_spear_paths_from(None, None, None);
+        assert_eq!(paths.config_dir, std::env::temp_dir().join("spear"));
+    }
+}
@@ -0,0 +1,484 @@
+use crate::config::peacock::{get_peacock_config_path, parse_ini};
+use crate::constants::SPEAR_PATH;
+use crate::core::health::{
+    Health, HealthTracker, PEACOCK_PORT, check_port_free, local_addr, monitor,
+};
+use crate::core::logs::{
+    LOG_BUFFER, LogSource, RotatingLog, Severity, get_logs_dir, parse_severity,
+};
+use crate::core::supervisor::{Crash, RestartPolicy, Supervisor, SupervisorState};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::os::windows::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use winapi::shared::minwindef::FALSE;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::jobapi2::AssignProcessToJobObject;
+use winapi::um::minwinbase::STILL_ACTIVE;
+use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+use winapi::um::winnt::{HANDLE, PROCESS_ALL_ACCESS, PROCESS_QUERY_LIMITED_INFORMATION};
+
+// The server is owned by the watchdog in HITMAN3.exe while the settings window
+// lives in Launcher.exe, so the two talk through files in the Peacock folder.
+
+/// Written by the watchdog every time it starts the node server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+    pub pid: u32,
+    pub started_at: String,
+    /// `options.ini` as the server read it on startup, keyed `section.key`.
+    pub options: BTreeMap<String, String>,
+    /// Set when the supervisor gave up restarting the server.
+    #[serde(default)]
+    pub failure: Option<String>,
+    /// Last health reported by the HTTP health check.
+    #[serde(default)]
+    pub health: Option<Health>,
+    /// HITMAN3.exe whose watchdog wrote this file.
+    #[serde(default)]
+    pub watchdog_pid: Option<u32>,
+}
+
+impl ServerInfo {
+    /// Whether the watchdog that wrote this is still running; if not, the
+    /// file is left over from an earlier game session.
+    fn has_live_watchdog(&self) -> bool {
+        self.watchdog_pid.is_some_and(is_process_alive)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerStatus {
+    NotRunning,
+    Running(ServerInfo),
+    Restarting,
+    Failed(String),
+}
+
+const MAX_CRASH_REPORTS: usize = 20;
+const SERVER_LOG_PREFIX: &str = "peacock";
+const SERVER_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+const SERVER_LOG_MAX_FILES: usize = 10;
+const HEALTH_INTERVAL: Duration = Duration::from_secs(1);
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Missed probes tolerated before a ready server counts as not responding.
+const HEALTH_FAILURES_ALLOWED: u32 = 3;
+/// The watchdog picks a restart request up within a second, so an older one
+/// was never going to be handled.
+const RESTART_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn server_info_path() -> PathBuf {
+    SPEAR_PATH.join("peacock").join("server.toml")
+}
+
+fn restart_request_path() -> PathBuf {
+    SPEAR_PATH.join("peacock").join("restart.request")
+}
+
+pub fn get_crash_reports_dir() -> PathBuf {
+    SPEAR_PATH.join("crashes")
+}
+
+fn write_server_info(info: &ServerInfo) -> std::io::Result<()> {
+    let content = toml::to_string(info).map_err(std::io::Error::other)?;
+    crate::config::atomic::write_atomic(&server_info_path(), &content)
+}
+
+fn read_server_info() -> Option<ServerInfo> {
+    let content = fs::read_to_string(server_info_path()).ok()?;
+    toml::from_str(&content).ok()
+}
+
+fn read_options_values() -> BTreeMap<String, String> {
+    let content = fs::read_to_string(get_peacock_config_path()).unwrap_or_default();
+    parse_ini(&content)
+        .into_iter()
+        .map(|e| (format!("{}.{}", e.section, e.key), e.value))
+        .collect()
+}
+
+fn is_process_alive(pid: u32) -> bool {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let mut exit_code = 0;
+        let ok = GetExitCodeProcess(handle, &mut exit_code);
+        CloseHandle(handle);
+        ok != 0 && exit_code == STILL_ACTIVE
+    }
+}
+
+fn assign_to_job(job_handle: HANDLE, pid: u32) {
+    let server_handle = unsafe { OpenProcess(PROCESS_ALL_ACCESS, FALSE, pid) };
+    if server_handle != 0 as HANDLE {
+        let assign_result = unsafe { AssignProcessToJobObject(job_handle, server_handle) };
+        if assign_result != 0 {
+            log::info!("[+] Server assigned to job object");
+        } else {
+            log::error!("[!] Failed to assign server to job object");
+        }
+        unsafe { CloseHandle(server_handle) };
+    } else {
+        log::error!("[!] Failed to open server process handle");
+    }
+}
+
+/// Copies one of the server's output streams into `server_log` line by line, tagging
+/// each line with its severity. Ends when the server closes the stream.
+fn forward_output(
+    stream: impl Read + Send + 'static,
+    server_log: Arc<Mutex<RotatingLog>>,
+    default: Severity,
+) {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buf = vec![];
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            let severity = parse_severity(line, default);
+            if severity == Severity::Error {
+                log::error!("[!] Peacock: {}", line);
+            }
+            let entry = format!(
+                "{} [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                severity.label(),
+                line
+            );
+            if let Err(e) = server_log.lock().unwrap().write_line(&entry) {
+                log::error!("[!] Failed to write Peacock server log: {}", e);
+            }
+        }
+    });
+}
+
+/// The last `limit` error lines the server logged, newest last, as far as
+/// the settings window's tail of the server log has read.
+pub fn recent_server_errors(limit: usize) -> Vec<String> {
+    LOG_BUFFER
+        .lock()
+        .unwrap()
+        .recent(LogSource::Peacock, Severity::Error, limit)
+}
+
+/// Starts `node chunk0.js` inside `job_handle`, records what it started with
+/// and starts health checks reporting to `health`.
+fn spawn_peacock_server(
+    job_handle: HANDLE,
+    health: &Sender<(u32, Health)>,
+) -> std::io::Result<Child> {
+    log::info!("[+] Starting node server");
+    check_port_free(PEACOCK_PORT)?;
+    let peacock_dir = SPEAR_PATH.join("peacock");
+    let options = read_options_values();
+    let mut server = std::process::Command::new(peacock_dir.join("nodedist").join("node.exe"))
+        .arg("chunk0.js")
+        .current_dir(&peacock_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .creation_flags(0x08000000)
+        .spawn()?;
+    let pid = server.id();
+    log::info!("[+] Node server started with PID {}", pid);
+    assign_to_job(job_handle, pid);
+
+    let server_log = Arc::new(Mutex::new(RotatingLog::new(
+        get_logs_dir(),
+        SERVER_LOG_PREFIX,
+        SERVER_LOG_MAX_BYTES,
+        SERVER_LOG_MAX_FILES,
+    )));
+    if let Some(stdout) = server.stdout.take() {
+        forward_output(stdout, server_log.clone(), Severity::Info);
+    }
+    if let Some(stderr) = server.stderr.take() {
+        forward_output(stderr, server_log, Severity::Error);
+    }
+
+    let status = health.clone();
+    std::thread::spawn(move || {
+        let tracker = HealthTracker::new(Instant::now(), STARTUP_TIMEOUT, HEALTH_FAILURES_ALLOWED);
+        monitor(
+            pid,
+            local_addr(PEACOCK_PORT),
+            HEALTH_INTERVAL,
+            tracker,
+            status,
+            || is_process_alive(pid),
+        );
+    });
+
+    let info = ServerInfo {
+        pid,
+        started_at: chrono::Local::now().to_rfc3339(),
+        options,
+        failure: None,
+        health: None,
+        watchdog_pid: Some(std::process::id()),
+    };
+    if let Err(e) = write_server_info(&info) {
+        log::error!("[!] Failed to write server info: {}", e);
+    }
+    Ok(server)
+}
+
+fn write_crash_report(crash: &Crash, state: &SupervisorState, policy: &RestartPolicy) {
+    let dir = get_crash_reports_dir();
+    // Backoff restarts can crash within the same second, so keep milliseconds
+    // and the attempt in the name.
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S_%3f").to_string();
+    let path = dir.join(format!("peacock-{}-{}.txt", timestamp, crash.attempt));
+    let outcome = match state {
+        SupervisorState::Backoff { attempt, until } => format!(
+            "restarting in {:.1}s (attempt {} of {})",
+            until
+                .saturating_duration_since(Instant::now())
+                .as_secs_f32(),
+            attempt,
+            policy.max_restarts
+        ),
+        SupervisorState::Failed { reason } => format!("gave up, server {}", reason),
+        _ => "none".to_string(),
+    };
+    let content = format!(
+        "Peacock server crash report\n\
+         time: {}\n\
+         pid: {}\n\
+         exit code: {}\n\
+         uptime: {:.1}s\n\
+         crash: {} in a row\n\
+         error: {}\n\
+         action: {}\n",
+        chrono::Local::now().to_rfc3339(),
+        crash.pid.map(|p| p.to_string()).unwrap_or("-".to_string()),
+        crash
+            .exit_code
+            .map(|c| c.to_string())
+            .unwrap_or("none".to_string()),
+        crash.uptime.as_secs_f32(),
+        crash.attempt,
+        crash.error.as_deref().unwrap_or("-"),
+        outcome
+    );
+    let result = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, content));
+    match result {
+        Ok(_) => log::info!("[+] Wrote crash report to {:?}", path),
+        Err(e) => log::error!("[!] Failed to write crash report: {}", e),
+    }
+
+    let mut reports: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    reports.sort();
+    if reports.len() > MAX_CRASH_REPORTS {
+        for old in &reports[..reports.len() - MAX_CRASH_REPORTS] {
+            let _ = fs::remove_file(old);
+        }
+    }
+}
+
+fn on_crash(crash: &Crash, supervisor: &Supervisor<Child>) {
+    log::error!(
+        "[!] Peacock server stopped (pid {:?}, exit code {:?}, crash {} in a row)",
+        crash.pid,
+        crash.exit_code,
+        crash.attempt
+    );
+    write_crash_report(crash, &supervisor.state, &supervisor.policy);
+    if let SupervisorState::Failed { reason } = &supervisor.state {
+        log::error!("[!] Peacock server {}, not restarting it again", reason);
+        let mut info = read_server_info().unwrap_or(ServerInfo {
+            pid: 0,
+            started_at: String::new(),
+            options: BTreeMap::new(),
+            failure: None,
+            health: None,
+            watchdog_pid: None,
+        });
+        info.watchdog_pid = Some(std::process::id());
+        info.failure = Some(format!(
+            "Peacock server {} and was restarted {} times",
+            reason, supervisor.policy.max_restarts
+        ));
+        if let Err(e) = write_server_info(&info) {
+            log::error!("[!] Failed to write server info: {}", e);
+        }
+    }
+}
+
+fn on_health(pid: u32, health: Health) {
+    match health {
+        Health::Starting => log::info!("[+] Waiting for Peacock server (PID {}) to answer", pid),
+        Health::Ready => log::info!("[+] Peacock server is ready on port {}", PEACOCK_PORT),
+        Health::TimedOut => log::error!(
+            "[!] Peacock server did not answer on port {} within {}s",
+            PEACOCK_PORT,
+            STARTUP_TIMEOUT.as_secs()
+        ),
+        Health::NotResponding => log::error!("[!] Peacock server stopped responding"),
+    }
+    let Some(mut info) = read_server_info().filter(|i| i.pid == pid) else {
+        return;
+    };
+    info.health = Some(health);
+    if let Err(e) = write_server_info(&info) {
+        log::error!("[!] Failed to write server info: {}", e);
+    }
+}
+
+/// Owns the node server for the lifetime of the game: restarts it with backoff
+/// when it crashes and whenever the settings window asks for a restart.
+pub fn supervise_peacock_server(job_handle: HANDLE) {
+    let (health_tx, health_rx) = channel();
+    let mut spawn = || spawn_peacock_server(job_handle, &health_tx);
+    let mut supervisor = Supervisor::new(RestartPolicy::default());
+    let _ = fs::remove_file(restart_request_path());
+    if let Some(crash) = supervisor.start(Instant::now(), &mut spawn) {
+        on_crash(&crash, &supervisor);
+    }
+    loop {
+        let crash = if restart_request_path().exists() {
+            log::info!("[+] Peacock server restart requested");
+            crate::config::profiles::apply_active_profile();
+            if let Some(mut info) = read_server_info().filter(|i| i.failure.is_some()) {
+                info.failure = None;
+                let _ = write_server_info(&info);
+            }
+            let crash = supervisor.restart(Instant::now(), &mut spawn);
+            let _ = fs::remove_file(restart_request_path());
+            crash
+        } else {
+            supervisor.tick(Instant::now(), &mut spawn)
+        };
+        if let Some(crash) = crash {
+            on_crash(&crash, &supervisor);
+        }
+        for (pid, health) in health_rx.try_iter() {
+            on_health(pid, health);
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Asks the watchdog in HITMAN3.exe to restart the server.
+pub fn request_restart() -> std::io::Result<()> {
+    log::info!("[+] Requesting Peacock server restart");
+    fs::write(restart_request_path(), "")
+}
+
+fn restart_request_age() -> Option<Duration> {
+    let modified = fs::metadata(restart_request_path()).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+/// What the watchdog last reported, ignoring files from a game that is no
+/// longer running. A restart request nobody is going to pick up is removed.
+pub fn peacock_server_status() -> ServerStatus {
+    let info = read_server_info().filter(|info| info.has_live_watchdog());
+    match restart_request_age() {
+        Some(age) if info.is_some() && age < RESTART_REQUEST_TIMEOUT => {
+            return ServerStatus::Restarting;
+        }
+        Some(_) => {
+            log::info!(
+                "[+] Dropping a Peacock server restart request the watchdog never picked up"
+            );
+            let _ = fs::remove_file(restart_request_path());
+        }
+        None => {}
+    }
+    match info {
+        Some(ServerInfo {
+            failure: Some(reason),
+            ..
+        }) => ServerStatus::Failed(reason),
+        Some(info) if is_process_alive(info.pid) => ServerStatus::Running(info),
+        _ => ServerStatus::NotRunning,
+    }
+}
+
+/// Options whose saved value differs from what the running server started
+/// with. Options the server started without are left out since it used its
+/// own defaults for them.
+pub fn pending_restart(
+    started_with: &BTreeMap<String, String>,
+    saved: &BTreeMap<String, String>,
+) -> Vec<String> {
+    saved
+        .iter()
+        .filter(|(key, value)| started_with.get(*key).is_some_and(|s| s != *value))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn only_changed_values_are_pending() {
+        let started_with = values(&[("peacock.jokes", "false"), ("peacock.liveSplit", "false")]);
+        let saved = values(&[("peacock.jokes", "true"), ("peacock.liveSplit", "false")]);
+        assert_eq!(
+            pending_restart(&started_with, &saved),
+            vec!["peacock.jokes".to_string()]
+        );
+    }
+
+    #[test]
+    fn options_unknown_at_startup_are_not_pending() {
+        let started_with = values(&[("peacock.jokes", "false")]);
+        let saved = values(&[("peacock.jokes", "false"), ("peacock.newFlag", "true")]);
+        assert!(pending_restart(&started_with, &saved).is_empty());
+    }
+
+    #[test]
+    fn server_info_round_trips_through_toml() {
+        let info = ServerInfo {
+            pid: 1234,
+            started_at: "2024-01-01T00:00:00+00:00".to_string(),
+            options: values(&[("peacock.jokes", "false")]),
+            failure: None,
+            health: Some(Health::Ready),
+            watchdog_pid: Some(5678),
+        };
+        let text = toml::to_string(&info).unwrap();
+        assert_eq!(toml::from_str::<ServerInfo>(&text).unwrap(), info);
+    }
+
+    #[test]
+    fn info_without_a_live_watchdog_is_stale() {
+        let mut info: ServerInfo =
+            toml::from_str("pid = 1234\nstarted_at = \"\"\n[options]\n").unwrap();
+        assert_eq!(info.watchdog_pid, None);
+        assert!(!info.has_live_watchdog());
+        info.watchdog_pid = Some(std::process::id());
+        assert!(info.has_live_watchdog());
+    }
+}
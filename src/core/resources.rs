@@ -1,7 +1,7 @@
-use crate::config::spear;
 use crate::constants::*;
+use crate::core::install::{DownloadProgress, InstallState};
+use crate::error::{Context, SpearError, SpearResult};
 use blake3;
-use image;
 use lazy_static::lazy_static;
 use std::fs;
 
@@ -11,44 +11,37 @@ use winapi::um::winnt::{MEM_COMMIT, PAGE_READONLY, PAGE_READWRITE};
 
 pub static REPLACEMENT_BG_DATA: &[u8] = include_bytes!("../../img/120.png");
 
-lazy_static! {
-    pub static ref REPLACEMENT_PIXELS: Vec<u8> = {
-        let rgba = image::load_from_memory(REPLACEMENT_BG_DATA)
-            .unwrap()
-            .to_rgba8();
-        rgba.into_raw()
-            .chunks_exact(4)
-            .flat_map(|chunk| [chunk[2], chunk[1], chunk[0], chunk[3]])
-            .collect()
-    };
-}
-
 pub static mut FAKE_BG_RESOURCE: *mut u8 = std::ptr::null_mut();
 
-pub fn allocate_fake_resource() {
+/// Copies the replacement background into read-only memory for the resource
+/// hooks to hand out. Until this succeeds the hooks leave resources alone.
+pub fn allocate_fake_resource() -> SpearResult<()> {
     unsafe {
-        FAKE_BG_RESOURCE = VirtualAlloc(
+        let resource = VirtualAlloc(
             std::ptr::null_mut(),
             REPLACEMENT_BG_DATA.len(),
             MEM_COMMIT,
             PAGE_READWRITE,
         ) as *mut u8;
-        if FAKE_BG_RESOURCE.is_null() {
-            panic!("Failed to allocate fake resource");
+        if resource.is_null() {
+            return Err(std::io::Error::last_os_error())
+                .context("Failed to allocate fake resource");
         }
         std::ptr::copy_nonoverlapping(
             REPLACEMENT_BG_DATA.as_ptr(),
-            FAKE_BG_RESOURCE,
+            resource,
             REPLACEMENT_BG_DATA.len(),
         );
         let mut old_protect = 0;
         VirtualProtect(
-            FAKE_BG_RESOURCE as *mut _,
+            resource as *mut _,
             REPLACEMENT_BG_DATA.len(),
             PAGE_READONLY,
             &mut old_protect,
         );
+        FAKE_BG_RESOURCE = resource;
     }
+    Ok(())
 }
 
 lazy_static! {
@@ -80,48 +73,116 @@ pub fn analyze_data_cached(data: &[u8]) {
     }
 }
 
-pub async fn peacock_download_release() -> Result<String, Box<dyn std::error::Error>> {
+/// Files a working Peacock install needs, relative to the Peacock folder.
+pub const REQUIRED_PEACOCK_FILES: &[&str] = &[
+    "version.txt",
+    "chunk0.js",
+    "nodedist/node.exe",
+    "PeacockPatcher.exe",
+];
+
+/// Problems with the Peacock install in `peacock_dir`; empty when it looks complete.
+pub fn check_installation(peacock_dir: &std::path::Path) -> Vec<String> {
+    if !peacock_dir.is_dir() {
+        return vec![format!("{} does not exist", peacock_dir.display())];
+    }
+    REQUIRED_PEACOCK_FILES
+        .iter()
+        .filter_map(|file| match fs::metadata(peacock_dir.join(file)) {
+            Ok(meta) if meta.len() > 0 => None,
+            Ok(_) => Some(format!("{} is empty", file)),
+            Err(_) => Some(format!("{} is missing", file)),
+        })
+        .collect()
+}
+
+/// Downloads and extracts the latest Peacock release, reporting progress
+/// through `on_state`. Returns the release tag.
+pub async fn peacock_download_release(on_state: impl Fn(InstallState)) -> SpearResult<String> {
     use octocrab::Octocrab;
     use reqwest;
-    use std::fs::{self, File};
-    use zip::ZipArchive;
 
     let peacock_dir = SPEAR_PATH.join("peacock");
-    fs::create_dir_all(&peacock_dir)?;
-
-    let config = spear::load_spear_config();
-    let mut repo_str = config.peacock_github_repo.clone();
-    let slash_pos = repo_str.find('/').unwrap();
+    fs::create_dir_all(&peacock_dir).context("Failed to create the Peacock folder")?;
+    crate::config::flag_changes::cache_installed_flag_definitions();
+
+    let full_repo = crate::config::runtime::get_peacock_repo();
+    let mut repo_str = full_repo.clone();
+    let slash_pos = repo_str
+        .find('/')
+        .context(format!("Invalid repo {:?}, expected owner/name", repo_str))?;
     let repo = repo_str.split_off(slash_pos + 1);
     let owner = repo_str;
 
-    let octocrab = Octocrab::builder().build()?;
-    let release = octocrab.repos(owner, repo).releases().get_latest().await?;
+    let context = format!("Failed to get the latest release of {}/{}", owner, repo);
+    let octocrab = Octocrab::builder()
+        .build()
+        .map_err(|e| SpearError::network(&context, e))?;
+    let release = octocrab
+        .repos(owner, repo)
+        .releases()
+        .get_latest()
+        .await
+        .map_err(|e| SpearError::network(&context, e))?;
     log::info!("Latest release: {}", release.tag_name);
 
-    std::fs::write(peacock_dir.join("version.txt"), &release.tag_name)?;
-
-    for asset in &release.assets {
-        if asset.name.to_lowercase().contains("linux") {
-            log::info!("Skipping asset: {}", asset.name);
-            continue;
-        }
+    let assets: Vec<_> = release
+        .assets
+        .iter()
+        .filter(|asset| {
+            let linux = asset.name.to_lowercase().contains("linux");
+            if linux {
+                log::info!("Skipping asset: {}", asset.name);
+            }
+            !linux
+        })
+        .collect();
+    let mut progress = DownloadProgress::new(assets.iter().map(|a| a.size.max(0) as u64).sum());
+    on_state(InstallState::Downloading(0.0));
+    for asset in assets {
         log::info!("Downloading asset: {}", asset.name);
-        let response = reqwest::get(asset.browser_download_url.clone()).await?;
-        let bytes = response.bytes().await?;
-        let mut file = File::create(peacock_dir.join(&asset.name))?;
-        std::io::Write::write_all(&mut file, &bytes)?;
+        let context = format!("Failed to download {}", asset.name);
+        let mut response = reqwest::get(asset.browser_download_url.clone())
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| SpearError::network(&context, e))?;
+        let mut file = fs::File::create(peacock_dir.join(&asset.name)).context(&context)?;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| SpearError::network(&context, e))?
+        {
+            std::io::Write::write_all(&mut file, &chunk).context(&context)?;
+            on_state(InstallState::Downloading(progress.add(chunk.len() as u64)));
+        }
     }
 
-    for entry in fs::read_dir(&peacock_dir)? {
+    on_state(InstallState::Extracting);
+    extract_release(&peacock_dir, &full_repo, &release.tag_name)
+        .context("Failed to extract the Peacock release")
+}
+
+/// Unpacks the downloaded archives in `peacock_dir` and flattens their
+/// `Peacock-*` folder into it. `repo.txt` and `version.txt` are only written
+/// once everything is in place, so a failed update never labels the old files
+/// with `repo` and `tag`.
+fn extract_release(
+    peacock_dir: &std::path::Path,
+    repo: &str,
+    tag: &str,
+) -> std::io::Result<String> {
+    use std::fs::File;
+    use zip::ZipArchive;
+
+    for entry in fs::read_dir(peacock_dir)? {
         let entry = entry?;
         let path = entry.path();
         if path.extension().and_then(|s| s.to_str()) == Some("zip") {
             log::info!("Extracting: {}", path.display());
             let file = File::open(&path)?;
-            let mut archive = ZipArchive::new(file)?;
+            let mut archive = ZipArchive::new(file).map_err(std::io::Error::other)?;
             for i in 0..archive.len() {
-                let mut file = archive.by_index(i)?;
+                let mut file = archive.by_index(i).map_err(std::io::Error::other)?;
                 let outpath = peacock_dir.join(file.name());
                 if file.name().ends_with('/') {
                     fs::create_dir_all(&outpath)?;
@@ -138,10 +199,10 @@ pub async fn peacock_download_release() -> Result<String, Box<dyn std::error::Er
             fs::remove_file(&path)?;
         }
 
-        for entry_result in fs::read_dir(&peacock_dir)? {
+        for entry_result in fs::read_dir(peacock_dir)? {
             let entry = entry_result?;
             if entry.file_type()?.is_dir() {
-                if entry.file_name().to_str().unwrap().contains("Peacock-") {
+                if entry.file_name().to_string_lossy().contains("Peacock-") {
                     let src_dir = entry.path();
                     fn copy_contents(
                         src: &std::path::Path,
@@ -160,12 +221,13 @@ pub async fn peacock_download_release() -> Result<String, Box<dyn std::error::Er
                         }
                         Ok(())
                     }
-                    copy_contents(&src_dir, &peacock_dir)?;
+                    copy_contents(&src_dir, peacock_dir)?;
                     fs::remove_dir_all(&src_dir)?;
                 }
             }
         }
     }
-
-    Ok(release.tag_name)
+    fs::write(peacock_dir.join("repo.txt"), repo)?;
+    fs::write(peacock_dir.join("version.txt"), tag)?;
+    Ok(tag.to_string())
 }
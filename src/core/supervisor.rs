@@ -0,0 +1,353 @@
+use std::time::{Duration, Instant};
+
+/// The parts of a child process the supervisor needs, so the state machine can
+/// be driven by a fake in tests.
+pub trait SupervisedChild {
+    fn id(&self) -> u32;
+    /// `Ok(Some(code))` once the process has exited; `code` is `None` when it
+    /// was killed without one.
+    fn try_wait(&mut self) -> std::io::Result<Option<Option<i32>>>;
+    /// Stops the process and waits for it to exit.
+    fn stop(&mut self) -> std::io::Result<()>;
+}
+
+impl SupervisedChild for std::process::Child {
+    fn id(&self) -> u32 {
+        std::process::Child::id(self)
+    }
+
+    fn try_wait(&mut self) -> std::io::Result<Option<Option<i32>>> {
+        Ok(std::process::Child::try_wait(self)?.map(|status| status.code()))
+    }
+
+    fn stop(&mut self) -> std::io::Result<()> {
+        self.kill()?;
+        self.wait().map(|_| ())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RestartPolicy {
+    /// Consecutive crashes allowed before giving up.
+    pub max_restarts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Running this long resets the crash count.
+    pub stable_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            stable_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Doubles with every attempt, starting at `base_delay`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SupervisorState {
+    NotStarted,
+    Running { pid: u32 },
+    Backoff { attempt: u32, until: Instant },
+    Failed { reason: String },
+}
+
+/// An unexpected exit, or a failed start, of the supervised process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crash {
+    pub pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    pub uptime: Duration,
+    pub attempt: u32,
+    pub error: Option<String>,
+}
+
+pub struct Supervisor<C> {
+    pub policy: RestartPolicy,
+    pub state: SupervisorState,
+    child: Option<C>,
+    started_at: Instant,
+    crashes: u32,
+}
+
+impl<C: SupervisedChild> Supervisor<C> {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            state: SupervisorState::NotStarted,
+            child: None,
+            started_at: Instant::now(),
+            crashes: 0,
+        }
+    }
+
+    /// Starts the process. A failed start counts as a crash.
+    pub fn start(
+        &mut self,
+        now: Instant,
+        spawn: &mut dyn FnMut() -> std::io::Result<C>,
+    ) -> Option<Crash> {
+        match spawn() {
+            Ok(child) => {
+                self.state = SupervisorState::Running { pid: child.id() };
+                self.child = Some(child);
+                self.started_at = now;
+                None
+            }
+            Err(e) => Some(self.record_crash(now, None, None, Some(e.to_string()))),
+        }
+    }
+
+    /// Stops the current process, if any, and starts a fresh one with the
+    /// crash count reset. Also the way out of `Failed`.
+    pub fn restart(
+        &mut self,
+        now: Instant,
+        spawn: &mut dyn FnMut() -> std::io::Result<C>,
+    ) -> Option<Crash> {
+        if let Some(mut child) = self.child.take() {
+            if let Err(e) = child.stop() {
+                log::error!("[!] Failed to stop supervised process: {}", e);
+            }
+        }
+        self.crashes = 0;
+        self.start(now, spawn)
+    }
+
+    /// Checks on the process and restarts it once its backoff has passed.
+    /// Returns the crash, if one was noticed during this call.
+    pub fn tick(
+        &mut self,
+        now: Instant,
+        spawn: &mut dyn FnMut() -> std::io::Result<C>,
+    ) -> Option<Crash> {
+        match self.state.clone() {
+            SupervisorState::Running { pid } => {
+                let child = self.child.as_mut()?;
+                let exit_code = match child.try_wait() {
+                    Ok(None) => {
+                        if now.duration_since(self.started_at) >= self.policy.stable_after {
+                            self.crashes = 0;
+                        }
+                        return None;
+                    }
+                    Ok(Some(code)) => code,
+                    Err(_) => None,
+                };
+                self.child = None;
+                Some(self.record_crash(now, Some(pid), exit_code, None))
+            }
+            SupervisorState::Backoff { until, .. } if now >= until => self.start(now, spawn),
+            _ => None,
+        }
+    }
+
+    fn record_crash(
+        &mut self,
+        now: Instant,
+        pid: Option<u32>,
+        exit_code: Option<i32>,
+        error: Option<String>,
+    ) -> Crash {
+        self.crashes += 1;
+        let crash = Crash {
+            pid,
+            exit_code,
+            uptime: pid
+                .map(|_| now.duration_since(self.started_at))
+                .unwrap_or_default(),
+            attempt: self.crashes,
+            error,
+        };
+        self.state = if self.crashes > self.policy.max_restarts {
+            SupervisorState::Failed {
+                reason: match (&crash.error, crash.exit_code) {
+                    (Some(e), _) => format!("could not be started: {}", e),
+                    (None, Some(code)) => format!("exited with code {}", code),
+                    (None, None) => "was terminated".to_string(),
+                },
+            }
+        } else {
+            SupervisorState::Backoff {
+                attempt: self.crashes,
+                until: now + self.policy.delay(self.crashes),
+            }
+        };
+        crash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Exits with `exit_code` once `exited` is set.
+    struct FakeChild {
+        pid: u32,
+        exited: Rc<RefCell<Option<i32>>>,
+        stopped: bool,
+    }
+
+    impl SupervisedChild for FakeChild {
+        fn id(&self) -> u32 {
+            self.pid
+        }
+
+        fn try_wait(&mut self) -> std::io::Result<Option<Option<i32>>> {
+            Ok(self.exited.borrow().map(Some))
+        }
+
+        fn stop(&mut self) -> std::io::Result<()> {
+            self.stopped = true;
+            Ok(())
+        }
+    }
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            max_restarts: 2,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            stable_after: Duration::from_secs(60),
+        }
+    }
+
+    /// Spawns fake children that share one exit switch, numbering their pids.
+    fn spawner(exited: Rc<RefCell<Option<i32>>>) -> impl FnMut() -> std::io::Result<FakeChild> {
+        let mut next_pid = 100;
+        move || {
+            next_pid += 1;
+            *exited.borrow_mut() = None;
+            Ok(FakeChild {
+                pid: next_pid,
+                exited: exited.clone(),
+                stopped: false,
+            })
+        }
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_maximum() {
+        let policy = policy();
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(4), Duration::from_secs(8));
+        assert_eq!(policy.delay(5), Duration::from_secs(10));
+        assert_eq!(policy.delay(40), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn crash_is_restarted_after_backoff() {
+        let exited = Rc::new(RefCell::new(None));
+        let mut spawn = spawner(exited.clone());
+        let mut supervisor = Supervisor::new(policy());
+        let t0 = Instant::now();
+        assert_eq!(supervisor.start(t0, &mut spawn), None);
+        assert_eq!(supervisor.state, SupervisorState::Running { pid: 101 });
+        assert_eq!(supervisor.tick(t0, &mut spawn), None);
+
+        *exited.borrow_mut() = Some(1);
+        let crash = supervisor
+            .tick(t0 + Duration::from_secs(5), &mut spawn)
+            .unwrap();
+        assert_eq!(crash.pid, Some(101));
+        assert_eq!(crash.exit_code, Some(1));
+        assert_eq!(crash.uptime, Duration::from_secs(5));
+        assert!(matches!(
+            supervisor.state,
+            SupervisorState::Backoff { attempt: 1, .. }
+        ));
+
+        // Still waiting out the backoff.
+        supervisor.tick(t0 + Duration::from_millis(5500), &mut spawn);
+        assert!(matches!(supervisor.state, SupervisorState::Backoff { .. }));
+        supervisor.tick(t0 + Duration::from_secs(6), &mut spawn);
+        assert_eq!(supervisor.state, SupervisorState::Running { pid: 102 });
+    }
+
+    #[test]
+    fn gives_up_after_max_restarts() {
+        let exited = Rc::new(RefCell::new(None));
+        let mut spawn = spawner(exited.clone());
+        let mut supervisor = Supervisor::new(policy());
+        let mut now = Instant::now();
+        supervisor.start(now, &mut spawn);
+        for attempt in 1..=3 {
+            *exited.borrow_mut() = Some(7);
+            now += Duration::from_secs(1);
+            let crash = supervisor.tick(now, &mut spawn).unwrap();
+            assert_eq!(crash.attempt, attempt);
+            now += Duration::from_secs(10);
+            supervisor.tick(now, &mut spawn);
+        }
+        assert_eq!(
+            supervisor.state,
+            SupervisorState::Failed {
+                reason: "exited with code 7".to_string()
+            }
+        );
+        assert_eq!(
+            supervisor.tick(now + Duration::from_secs(60), &mut spawn),
+            None
+        );
+    }
+
+    #[test]
+    fn stable_run_resets_crash_count() {
+        let exited = Rc::new(RefCell::new(None));
+        let mut spawn = spawner(exited.clone());
+        let mut supervisor = Supervisor::new(policy());
+        let mut now = Instant::now();
+        supervisor.start(now, &mut spawn);
+        for _ in 0..5 {
+            now += Duration::from_secs(61);
+            supervisor.tick(now, &mut spawn);
+            *exited.borrow_mut() = Some(1);
+            let crash = supervisor.tick(now, &mut spawn).unwrap();
+            assert_eq!(crash.attempt, 1);
+            now += Duration::from_secs(1);
+            supervisor.tick(now, &mut spawn);
+        }
+        assert!(matches!(supervisor.state, SupervisorState::Running { .. }));
+    }
+
+    #[test]
+    fn failed_spawns_count_as_crashes() {
+        let mut spawn =
+            || -> std::io::Result<FakeChild> { Err(std::io::Error::other("missing node.exe")) };
+        let mut supervisor = Supervisor::new(policy());
+        let mut now = Instant::now();
+        let crash = supervisor.start(now, &mut spawn).unwrap();
+        assert_eq!(crash.error.as_deref(), Some("missing node.exe"));
+        for _ in 0..2 {
+            now += Duration::from_secs(10);
+            supervisor.tick(now, &mut spawn);
+        }
+        assert!(matches!(supervisor.state, SupervisorState::Failed { .. }));
+    }
+
+    #[test]
+    fn manual_restart_recovers_from_failed() {
+        let exited = Rc::new(RefCell::new(None));
+        let mut spawn = spawner(exited.clone());
+        let mut supervisor = Supervisor::new(policy());
+        supervisor.state = SupervisorState::Failed {
+            reason: "exited with code 1".to_string(),
+        };
+        supervisor.restart(Instant::now(), &mut spawn);
+        assert_eq!(supervisor.state, SupervisorState::Running { pid: 101 });
+    }
+}
@@ -0,0 +1,32 @@
+//! Helpers shared by the tests that touch the filesystem.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty folder under the system temp dir that is removed again on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` keeps tests that run in parallel out of each other's way.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("spear-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong inside spear. Each variant carries what spear
+/// was doing when it failed, so one log line or message is enough to act on.
+#[derive(Debug)]
+pub enum SpearError {
+    Io {
+        context: String,
+        source: io::Error,
+    },
+    /// A config, bundle, flags or archive file that couldn't be read or written.
+    Format {
+        context: String,
+        message: String,
+    },
+    Network {
+        context: String,
+        message: String,
+    },
+    Image {
+        context: String,
+        source: image::ImageError,
+    },
+    Pe {
+        context: String,
+        message: String,
+    },
+    Hook {
+        context: String,
+        message: String,
+    },
+    /// Something spear expected to exist doesn't.
+    Missing {
+        context: String,
+    },
+}
+
+pub type SpearResult<T> = Result<T, SpearError>;
+
+impl SpearError {
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        SpearError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    pub fn format(context: impl Into<String>, message: impl fmt::Display) -> Self {
+        SpearError::Format {
+            context: context.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn network(context: impl Into<String>, message: impl fmt::Display) -> Self {
+        SpearError::Network {
+            context: context.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn hook(context: impl Into<String>, message: impl fmt::Display) -> Self {
+        SpearError::Hook {
+            context: context.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn missing(context: impl Into<String>) -> Self {
+        SpearError::Missing {
+            context: context.into(),
+        }
+    }
+}
+
+impl fmt::Display for SpearError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpearError::Io { context, source } => write!(f, "{}: {}", context, source),
+            SpearError::Image { context, source } => write!(f, "{}: {}", context, source),
+            SpearError::Format { context, message }
+            | SpearError::Network { context, message }
+            | SpearError::Pe { context, message }
+            | SpearError::Hook { context, message } => write!(f, "{}: {}", context, message),
+            SpearError::Missing { context } => write!(f, "{}", context),
+        }
+    }
+}
+
+impl std::error::Error for SpearError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SpearError::Io { source, .. } => Some(source),
+            SpearError::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches what spear was doing to a lower-level error.
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|source| SpearError::io(context, source))
+    }
+}
+
+impl<T> Context<T> for Result<T, image::ImageError> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|source| SpearError::Image {
+            context: context.into(),
+            source,
+        })
+    }
+}
+
+impl<T> Context<T> for Result<T, toml::de::Error> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|e| SpearError::format(context, e))
+    }
+}
+
+impl<T> Context<T> for Result<T, toml::ser::Error> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|e| SpearError::format(context, e))
+    }
+}
+
+impl<T> Context<T> for Result<T, zip::result::ZipError> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|e| SpearError::format(context, e))
+    }
+}
+
+impl<T> Context<T> for Result<T, pelite::Error> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.map_err(|e| SpearError::Pe {
+            context: context.into(),
+            message: e.to_string(),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: impl Into<String>) -> SpearResult<T> {
+        self.ok_or_else(|| SpearError::missing(context))
+    }
+}
+
+/// Top-level policy: a failure is logged and shown to the user, and the host
+/// process keeps running without the part that failed.
+pub fn report(error: &SpearError) {
+    log::error!("[!] {}", error);
+    let text: Vec<u16> = format!("{}\0", error).encode_utf16().collect();
+    // Off-thread so the message box never blocks the launcher or the game.
+    std::thread::spawn(move || unsafe {
+        winapi::um::winuser::MessageBoxW(
+            std::ptr::null_mut(),
+            text.as_ptr(),
+            widestring::u16cstr!("spear").as_ptr(),
+            winapi::um::winuser::MB_OK | winapi::um::winuser::MB_ICONWARNING,
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_is_prefixed() {
+        let result: io::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        let error = result.context("Failed to read options.ini").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to read options.ini: gone");
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn missing_values_become_errors() {
+        let error = None::<u32>.context("No .text section").unwrap_err();
+        assert!(matches!(error, SpearError::Missing { .. }));
+        assert_eq!(error.to_string(), "No .text section");
+    }
+}
@@ -359,9 +359,12 @@ fn waveOutUnprepareHeader() {}
 #[unsafe(no_mangle)] //winmm.dll
 fn waveOutWrite() {}
 use winapi::ctypes::c_void;
-use winapi::shared::minwindef::UINT;
+use winapi::shared::minwindef::{HINSTANCE, UINT};
 use winapi::shared::ntdef::LONG;
+use winapi::shared::windef::HWND;
 use winapi::um::libloaderapi::GetProcAddress;
+use winapi::um::winnt::LPSTR;
+use winapi::um::winuser::{MB_ICONINFORMATION, MB_ICONWARNING, MB_OK, MessageBoxW};
 
 #[unsafe(no_mangle)]
 pub extern "system" fn CloseDriver(hDriver: *mut c_void, lParam1: LONG, lParam2: LONG) -> UINT {
@@ -376,3 +379,33 @@ pub extern "system" fn CloseDriver(hDriver: *mut c_void, lParam1: LONG, lParam2:
         }
     }
 }
+
+/// `rundll32 "<HITMAN 3 folder>\winmm.dll",CreateDiagnosticsBundle` writes a
+/// diagnostics bundle when the overlay or settings window won't open.
+#[unsafe(no_mangle)]
+pub extern "system" fn CreateDiagnosticsBundle(
+    hwnd: HWND,
+    _hinst: HINSTANCE,
+    _cmd_line: LPSTR,
+    _show: i32,
+) {
+    let (text, icon) = match crate::core::diagnostics::create_diagnostics_bundle() {
+        Ok(path) => (
+            format!("Diagnostics bundle saved to {}", path.display()),
+            MB_ICONINFORMATION,
+        ),
+        Err(e) => {
+            log::error!("[!] {}", e);
+            (e.to_string(), MB_ICONWARNING)
+        }
+    };
+    let text: Vec<u16> = format!("{}\0", text).encode_utf16().collect();
+    unsafe {
+        MessageBoxW(
+            hwnd,
+            text.as_ptr(),
+            widestring::u16cstr!("spear").as_ptr(),
+            MB_OK | icon,
+        );
+    }
+}
@@ -1,39 +1,71 @@
+use crate::config::layout::{
+    get_layout_path, layout_spec, reload_layout, save_layout, set_layout_spec, validate_layout,
+};
+use crate::core::logs::ERROR_COLOR;
+
 use lazy_static::lazy_static;
 use std::sync::Mutex;
 
-#[derive(Clone)]
-pub struct DebugParams {
-    pub font_height: i32,
-    pub font_weight: i32,
-    pub text_width: f32,
-    pub text_height: f32,
-    pub text_pos_x: f32,
-    pub text_pos_y: f32,
-    pub changed: bool,
-    pub version_font_height: i32,
-    pub version_font_weight: i32,
-    pub version_text_width: f32,
-    pub version_text_height: f32,
-    pub version_text_pos_x: f32,
-    pub version_text_pos_y: f32,
-    pub version_changed: bool,
+lazy_static! {
+    /// Result of the last save from the layout editor.
+    static ref LAYOUT_STATUS: Mutex<Option<String>> = Mutex::new(None);
 }
 
-lazy_static! {
-    pub static ref DEBUG_PARAMS: Mutex<DebugParams> = Mutex::new(DebugParams {
-        font_height: 30,
-        font_weight: 400,
-        text_width: 150.0,
-        text_height: 21.0,
-        text_pos_x: 45.0,
-        text_pos_y: 9.0,
-        changed: true,
-        version_font_height: 30,
-        version_font_weight: 400,
-        version_text_width: 27.0,
-        version_text_height: 15.0,
-        version_text_pos_x: 575.0,
-        version_text_pos_y: 315.0,
-        version_changed: true,
+/// Drag values for every entry of `layout.toml`. Edits show on the overlay
+/// right away and are written back with "Save layout".
+pub fn layout_editor(ui: &mut egui::Ui) {
+    let mut spec = layout_spec();
+    let mut changed = false;
+    for (name, placement) in spec.placements_mut() {
+        ui.label(name);
+        ui.horizontal(|ui| {
+            for (label, value) in [
+                ("x", &mut placement.x),
+                ("y", &mut placement.y),
+                ("w", &mut placement.width),
+                ("h", &mut placement.height),
+            ] {
+                changed |= ui
+                    .add(egui::DragValue::new(value).prefix(format!("{}: ", label)))
+                    .changed();
+            }
+        });
+    }
+    for (name, font) in spec.fonts_mut() {
+        ui.label(name);
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::DragValue::new(&mut font.height).prefix("height: "))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut font.weight).prefix("weight: "))
+                .changed();
+        });
+    }
+    for error in validate_layout(&spec) {
+        ui.colored_label(ERROR_COLOR, error);
+    }
+    ui.horizontal(|ui| {
+        if ui
+            .button("Save layout")
+            .on_hover_text(get_layout_path().display().to_string())
+            .clicked()
+        {
+            let status = match save_layout(&spec) {
+                Ok(()) => format!("Saved to {}", get_layout_path().display()),
+                Err(e) => e.to_string(),
+            };
+            *LAYOUT_STATUS.lock().unwrap() = Some(status);
+        }
+        if ui.button("Reload layout").clicked() {
+            reload_layout();
+            *LAYOUT_STATUS.lock().unwrap() = None;
+        }
     });
+    if let Some(status) = &*LAYOUT_STATUS.lock().unwrap() {
+        ui.label(status);
+    }
+    if changed {
+        set_layout_spec(spec);
+    }
 }
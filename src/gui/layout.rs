@@ -0,0 +1,245 @@
+use egui::{Rect, Vec2, vec2};
+use serde::{Deserialize, Serialize};
+
+/// Built-in `layout.toml`; a user file only needs the values it changes.
+pub const DEFAULT_LAYOUT: &str = include_str!("layout.toml");
+
+/// Windows' 100% scaling.
+pub const BASE_DPI: u32 = 96;
+/// Launcher client area at 100% scaling; all placements are in these units.
+pub const DESIGN_SIZE: Vec2 = vec2(
+    crate::constants::TARGET_WIDTH as f32,
+    crate::constants::TARGET_HEIGHT as f32,
+);
+
+/// Corner of the launcher client rect a placement is measured from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where an element sits, in design units: `x`/`y` go from the anchor corner
+/// to the element's top-left, so bottom/right anchors use negative offsets.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Placement {
+    pub anchor: Anchor,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Placement {
+    pub fn resolve(&self, client: Vec2, scale: f32) -> Rect {
+        let corner = match self.anchor {
+            Anchor::TopLeft => Vec2::ZERO,
+            Anchor::TopRight => vec2(client.x, 0.0),
+            Anchor::BottomLeft => vec2(0.0, client.y),
+            Anchor::BottomRight => client,
+        };
+        Rect::from_min_size(
+            (corner + vec2(self.x, self.y) * scale).to_pos2(),
+            vec2(self.width, self.height) * scale,
+        )
+    }
+}
+
+/// GDI font for a text texture. `height` is in design units.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Font {
+    pub height: i32,
+    pub weight: i32,
+}
+
+impl Font {
+    pub fn scaled(&self, scale: f32) -> Self {
+        Self {
+            height: (self.height as f32 * scale).round() as i32,
+            weight: self.weight,
+        }
+    }
+}
+
+/// Placements and fonts of every overlay element, as read from `layout.toml`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutSpec {
+    /// Covers the launcher's own PLAY button.
+    pub play_button: Placement,
+    /// Relative to the top-left of `play_button`.
+    pub play_icon: Placement,
+    /// Relative to the top-left of `play_button`.
+    pub play_text: Placement,
+    pub play_text_font: Font,
+    pub settings_button: Placement,
+    pub profile: Placement,
+    pub version: Placement,
+    pub version_font: Font,
+    pub progress_bar: Placement,
+}
+
+impl Default for LayoutSpec {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_LAYOUT).expect("built-in layout.toml is valid")
+    }
+}
+
+impl LayoutSpec {
+    pub fn placements_mut(&mut self) -> [(&'static str, &mut Placement); 7] {
+        [
+            ("play_button", &mut self.play_button),
+            ("play_icon", &mut self.play_icon),
+            ("play_text", &mut self.play_text),
+            ("settings_button", &mut self.settings_button),
+            ("profile", &mut self.profile),
+            ("version", &mut self.version),
+            ("progress_bar", &mut self.progress_bar),
+        ]
+    }
+
+    pub fn fonts_mut(&mut self) -> [(&'static str, &mut Font); 2] {
+        [
+            ("play_text_font", &mut self.play_text_font),
+            ("version_font", &mut self.version_font),
+        ]
+    }
+}
+
+/// Overlay geometry in physical pixels for one client size and scale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub client: Vec2,
+    pub scale: f32,
+    pub play_button: Rect,
+    pub play_icon: Rect,
+    pub play_text: Rect,
+    pub play_text_font: Font,
+    pub settings_button: Rect,
+    pub profile: Rect,
+    pub version: Rect,
+    pub version_font: Font,
+    pub progress_bar: Rect,
+}
+
+impl Layout {
+    pub fn compute(spec: &LayoutSpec, client: Vec2, scale: f32) -> Self {
+        let play_button = spec.play_button.resolve(client, scale);
+        Self {
+            client,
+            scale,
+            play_button,
+            play_icon: spec
+                .play_icon
+                .resolve(play_button.size(), scale)
+                .translate(play_button.min.to_vec2()),
+            play_text: spec
+                .play_text
+                .resolve(play_button.size(), scale)
+                .translate(play_button.min.to_vec2()),
+            play_text_font: spec.play_text_font.scaled(scale),
+            settings_button: spec.settings_button.resolve(client, scale),
+            profile: spec.profile.resolve(client, scale),
+            version: spec.version.resolve(client, scale),
+            version_font: spec.version_font.scaled(scale),
+            progress_bar: spec.progress_bar.resolve(client, scale),
+        }
+    }
+
+    pub fn rects(&self) -> [(&'static str, Rect); 7] {
+        [
+            ("play_button", self.play_button),
+            ("play_icon", self.play_icon),
+            ("play_text", self.play_text),
+            ("settings_button", self.settings_button),
+            ("profile", self.profile),
+            ("version", self.version),
+            ("progress_bar", self.progress_bar),
+        ]
+    }
+
+    /// Whether the launcher changed enough to lay the overlay out again.
+    pub fn is_stale(&self, client: Vec2, scale: f32) -> bool {
+        self.client != client || self.scale != scale
+    }
+
+    /// A length in design units, in physical pixels.
+    pub fn px(&self, design: f32) -> f32 {
+        design * self.scale
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::compute(&LayoutSpec::default(), DESIGN_SIZE, 1.0)
+    }
+}
+
+/// Scale factor for a window DPI; 0 means the DPI couldn't be read.
+pub fn scale_for_dpi(dpi: u32) -> f32 {
+    if dpi == 0 {
+        return 1.0;
+    }
+    dpi as f32 / BASE_DPI as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::pos2;
+
+    #[test]
+    fn default_layout_matches_the_launcher() {
+        let layout = Layout::default();
+        assert_eq!(
+            layout.play_button,
+            Rect::from_min_size(pos2(79.0, 89.0), vec2(222.0, 39.0))
+        );
+        assert_eq!(layout.play_icon.min, pos2(88.0, 96.0));
+        assert_eq!(
+            layout.play_text,
+            Rect::from_min_size(pos2(124.0, 98.0), vec2(150.0, 21.0))
+        );
+        assert_eq!(layout.settings_button.min, pos2(9.0, 309.0));
+        assert_eq!(layout.version.min, pos2(575.0, 315.0));
+        assert_eq!(layout.progress_bar.min, pos2(398.0, 334.0));
+    }
+
+    #[test]
+    fn layout_follows_dpi() {
+        let scale = scale_for_dpi(144);
+        assert_eq!(scale, 1.5);
+        let layout = Layout::compute(&LayoutSpec::default(), DESIGN_SIZE * scale, scale);
+        assert_eq!(
+            layout.play_button,
+            Rect::from_min_size(pos2(118.5, 133.5), vec2(333.0, 58.5))
+        );
+        assert_eq!(layout.play_icon.min, pos2(132.0, 144.0));
+        assert_eq!(layout.version.min, pos2(862.5, 472.5));
+        assert_eq!(layout.play_text_font.height, 45);
+        assert_eq!(layout.px(13.0), 19.5);
+    }
+
+    #[test]
+    fn corners_track_the_client_rect() {
+        let client = vec2(700.0, 400.0);
+        let layout = Layout::compute(&LayoutSpec::default(), client, 1.0);
+        assert_eq!(layout.play_button.min, pos2(79.0, 89.0));
+        assert_eq!(layout.settings_button.min, pos2(9.0, 365.0));
+        assert_eq!(layout.progress_bar.max, pos2(690.0, 393.0));
+    }
+
+    #[test]
+    fn relayout_only_on_change() {
+        let layout = Layout::default();
+        assert!(!layout.is_stale(DESIGN_SIZE, 1.0));
+        assert!(layout.is_stale(DESIGN_SIZE, 1.25));
+        assert!(layout.is_stale(vec2(600.0, 344.0), 1.0));
+        assert_eq!(scale_for_dpi(0), 1.0);
+    }
+}
@@ -2,12 +2,16 @@
 pub mod debug;
 #[allow(dead_code)]
 pub mod gdi;
+pub mod layout;
+pub mod navigation;
+pub mod notifications;
 #[allow(dead_code)]
 pub mod overlay;
 #[allow(dead_code)]
 pub mod overlay_thread;
 pub mod overlay_ui;
 pub mod overlay_utils;
+pub mod search;
 pub mod settings;
 
 use lazy_static::lazy_static;
@@ -0,0 +1,343 @@
+use egui_overlay::egui_window_glfw_passthrough::glfw;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+use winapi::shared::minwindef::{LPARAM, LRESULT, WPARAM};
+use winapi::shared::windef::HWND;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::um::winuser::{
+    CallNextHookEx, GetForegroundWindow, GetKeyState, GetWindowThreadProcessId, HC_ACTION,
+    SetWindowsHookExW, VK_DOWN, VK_ESCAPE, VK_F6, VK_LEFT, VK_RETURN, VK_RIGHT, VK_SHIFT, VK_SPACE,
+    VK_TAB, VK_UP, WH_KEYBOARD,
+};
+
+/// How far a stick has to be pushed to count as a d-pad press.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Overlay buttons that can take focus, in focus order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavTarget {
+    Play,
+    Settings,
+}
+
+const FOCUS_ORDER: [NavTarget; 2] = [NavTarget::Play, NavTarget::Settings];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavInput {
+    Next,
+    Previous,
+    Activate,
+    Cancel,
+    Launch,
+    /// Moves focus between the launcher and the overlay buttons.
+    Toggle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavAction {
+    Play,
+    OpenSettings,
+}
+
+/// Which overlay button has keyboard/controller focus. Nothing is focused
+/// until the first navigation input, so mouse users never see a focus ring.
+#[derive(Default)]
+pub struct Navigation {
+    focus: Option<NavTarget>,
+}
+
+impl Navigation {
+    pub fn focus(&self) -> Option<NavTarget> {
+        self.focus
+    }
+
+    /// Whether spear takes `input` from the launcher right now. Until one of
+    /// spear's buttons has focus, only [`NavInput::Toggle`] is taken so the
+    /// launcher's own keyboard handling keeps working.
+    pub fn consumes(&self, input: NavInput) -> bool {
+        match input {
+            NavInput::Toggle => true,
+            _ => self.focus.is_some(),
+        }
+    }
+
+    pub fn handle(&mut self, input: NavInput) -> Option<NavAction> {
+        match input {
+            NavInput::Next | NavInput::Previous => {
+                let len = FOCUS_ORDER.len();
+                let index = match self.focus {
+                    None => 0,
+                    Some(target) => {
+                        let current = FOCUS_ORDER.iter().position(|t| *t == target).unwrap_or(0);
+                        if input == NavInput::Next {
+                            (current + 1) % len
+                        } else {
+                            (current + len - 1) % len
+                        }
+                    }
+                };
+                self.focus = Some(FOCUS_ORDER[index]);
+                None
+            }
+            NavInput::Activate => self.focus.map(|target| match target {
+                NavTarget::Play => NavAction::Play,
+                NavTarget::Settings => NavAction::OpenSettings,
+            }),
+            NavInput::Cancel => {
+                self.focus = None;
+                None
+            }
+            NavInput::Launch => {
+                self.focus = Some(NavTarget::Play);
+                Some(NavAction::Play)
+            }
+            NavInput::Toggle => {
+                self.focus = match self.focus {
+                    None => Some(NavTarget::Play),
+                    Some(_) => None,
+                };
+                None
+            }
+        }
+    }
+}
+
+/// Turns held inputs into presses, so holding a key or button acts once.
+#[derive(Default)]
+pub struct InputEdges {
+    held: Vec<NavInput>,
+}
+
+impl InputEdges {
+    /// Returns the inputs in `held` that weren't held on the previous update.
+    pub fn update(&mut self, held: Vec<NavInput>) -> Vec<NavInput> {
+        let pressed = held
+            .iter()
+            .filter(|input| !self.held.contains(input))
+            .copied()
+            .collect();
+        self.held = held;
+        pressed
+    }
+}
+
+lazy_static! {
+    static ref NAVIGATION: Mutex<Navigation> = Mutex::new(Navigation::default());
+    static ref EDGES: Mutex<InputEdges> = Mutex::new(InputEdges::default());
+    static ref PENDING_ACTION: Mutex<Option<NavAction>> = Mutex::new(None);
+    static ref KEYBOARD_HOOKED: Mutex<bool> = Mutex::new(false);
+    /// Keys whose press spear handled, until they are released.
+    static ref SWALLOWED_KEYS: Mutex<Vec<i32>> = Mutex::new(vec![]);
+}
+
+fn key_input(vk: i32) -> Option<NavInput> {
+    Some(match vk {
+        VK_TAB if unsafe { GetKeyState(VK_SHIFT) } < 0 => NavInput::Previous,
+        VK_TAB | VK_DOWN | VK_RIGHT => NavInput::Next,
+        VK_UP | VK_LEFT => NavInput::Previous,
+        VK_RETURN | VK_SPACE => NavInput::Activate,
+        VK_ESCAPE => NavInput::Cancel,
+        VK_F6 => NavInput::Toggle,
+        _ => return None,
+    })
+}
+
+/// Sees key messages before the window they were sent to. Keys spear acts on
+/// are dropped, along with their repeats and key-up, so the launcher never
+/// handles the same press.
+unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION
+        && let Some(input) = key_input(wparam as i32)
+    {
+        let vk = wparam as i32;
+        let was_down = lparam as u32 & (1 << 30) != 0;
+        let released = lparam as u32 & (1 << 31) != 0;
+        let mut swallowed = SWALLOWED_KEYS.lock().unwrap();
+        if released || was_down {
+            if let Some(index) = swallowed.iter().position(|key| *key == vk) {
+                if released {
+                    swallowed.remove(index);
+                }
+                return 1;
+            }
+        } else {
+            let mut navigation = NAVIGATION.lock().unwrap();
+            if navigation.consumes(input) {
+                if let Some(action) = navigation.handle(input) {
+                    *PENDING_ACTION.lock().unwrap() = Some(action);
+                }
+                swallowed.push(vk);
+                return 1;
+            }
+        }
+    }
+    unsafe { CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam) }
+}
+
+/// Hooks the keyboard on the launcher's UI thread and on this one, since key
+/// messages go to whichever thread owns the focused window.
+fn install_keyboard_hooks(main_hwnd: HWND) {
+    let launcher_thread = unsafe { GetWindowThreadProcessId(main_hwnd, std::ptr::null_mut()) };
+    let overlay_thread = unsafe { GetCurrentThreadId() };
+    let mut threads = vec![launcher_thread];
+    if overlay_thread != launcher_thread {
+        threads.push(overlay_thread);
+    }
+    for thread in threads {
+        let hook = unsafe {
+            SetWindowsHookExW(
+                WH_KEYBOARD,
+                Some(keyboard_hook),
+                std::ptr::null_mut(),
+                thread,
+            )
+        };
+        if hook.is_null() {
+            log::error!("[!] Failed to hook the keyboard on thread {}", thread);
+        } else {
+            log::info!("[+] Hooked the keyboard on thread {}", thread);
+        }
+    }
+}
+
+fn held_gamepad_buttons(glfw: &glfw::Glfw) -> Vec<NavInput> {
+    use glfw::{Action, GamepadAxis, GamepadButton, JoystickId};
+
+    let mut held = vec![];
+    for id in [
+        JoystickId::Joystick1,
+        JoystickId::Joystick2,
+        JoystickId::Joystick3,
+        JoystickId::Joystick4,
+    ] {
+        let joystick = glfw.get_joystick(id);
+        if !joystick.is_gamepad() {
+            continue;
+        }
+        let Some(state) = joystick.get_gamepad_state() else {
+            continue;
+        };
+        for (button, input) in [
+            (GamepadButton::ButtonDpadUp, NavInput::Previous),
+            (GamepadButton::ButtonDpadLeft, NavInput::Previous),
+            (GamepadButton::ButtonDpadDown, NavInput::Next),
+            (GamepadButton::ButtonDpadRight, NavInput::Next),
+            (GamepadButton::ButtonA, NavInput::Activate),
+            (GamepadButton::ButtonB, NavInput::Cancel),
+            (GamepadButton::ButtonStart, NavInput::Launch),
+        ] {
+            if state.get_button_state(button) == Action::Press {
+                held.push(input);
+            }
+        }
+        let (x, y) = (
+            state.get_axis(GamepadAxis::AxisLeftX),
+            state.get_axis(GamepadAxis::AxisLeftY),
+        );
+        if x < -STICK_THRESHOLD || y < -STICK_THRESHOLD {
+            held.push(NavInput::Previous);
+        }
+        if x > STICK_THRESHOLD || y > STICK_THRESHOLD {
+            held.push(NavInput::Next);
+        }
+    }
+    held
+}
+
+/// Reads any gamepads while the launcher is the foreground window, moving
+/// focus and queueing an action for [`take_action`]. The keyboard is read by
+/// hooks installed on the first call.
+pub fn poll(glfw: &glfw::Glfw, main_hwnd: HWND) {
+    {
+        let mut hooked = KEYBOARD_HOOKED.lock().unwrap();
+        if !*hooked {
+            install_keyboard_hooks(main_hwnd);
+            *hooked = true;
+        }
+    }
+    let held = if unsafe { GetForegroundWindow() } == main_hwnd {
+        held_gamepad_buttons(glfw)
+    } else {
+        vec![]
+    };
+    let pressed = EDGES.lock().unwrap().update(held);
+    let mut navigation = NAVIGATION.lock().unwrap();
+    for input in pressed {
+        if let Some(action) = navigation.handle(input) {
+            *PENDING_ACTION.lock().unwrap() = Some(action);
+        }
+    }
+}
+
+pub fn focus() -> Option<NavTarget> {
+    NAVIGATION.lock().unwrap().focus()
+}
+
+pub fn take_action() -> Option<NavAction> {
+    PENDING_ACTION.lock().unwrap().take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_cycles_through_buttons() {
+        let mut nav = Navigation::default();
+        assert_eq!(nav.focus(), None);
+        assert_eq!(nav.handle(NavInput::Activate), None);
+        nav.handle(NavInput::Next);
+        assert_eq!(nav.focus(), Some(NavTarget::Play));
+        nav.handle(NavInput::Next);
+        assert_eq!(nav.focus(), Some(NavTarget::Settings));
+        nav.handle(NavInput::Next);
+        assert_eq!(nav.focus(), Some(NavTarget::Play));
+        nav.handle(NavInput::Previous);
+        assert_eq!(nav.focus(), Some(NavTarget::Settings));
+        assert_eq!(
+            nav.handle(NavInput::Activate),
+            Some(NavAction::OpenSettings)
+        );
+        nav.handle(NavInput::Cancel);
+        assert_eq!(nav.focus(), None);
+    }
+
+    #[test]
+    fn keys_pass_through_without_focus() {
+        let mut nav = Navigation::default();
+        for input in [
+            NavInput::Next,
+            NavInput::Previous,
+            NavInput::Activate,
+            NavInput::Cancel,
+        ] {
+            assert!(!nav.consumes(input), "{:?}", input);
+        }
+        assert!(nav.consumes(NavInput::Toggle));
+        nav.handle(NavInput::Toggle);
+        assert_eq!(nav.focus(), Some(NavTarget::Play));
+        assert!(nav.consumes(NavInput::Next));
+        assert!(nav.consumes(NavInput::Activate));
+        nav.handle(NavInput::Toggle);
+        assert_eq!(nav.focus(), None);
+    }
+
+    #[test]
+    fn launch_works_without_focus() {
+        let mut nav = Navigation::default();
+        assert_eq!(nav.handle(NavInput::Launch), Some(NavAction::Play));
+        assert_eq!(nav.focus(), Some(NavTarget::Play));
+    }
+
+    #[test]
+    fn held_inputs_act_once() {
+        let mut edges = InputEdges::default();
+        assert_eq!(edges.update(vec![NavInput::Next]), vec![NavInput::Next]);
+        assert_eq!(
+            edges.update(vec![NavInput::Next, NavInput::Activate]),
+            vec![NavInput::Activate]
+        );
+        assert_eq!(edges.update(vec![]), vec![]);
+        assert_eq!(edges.update(vec![NavInput::Next]), vec![NavInput::Next]);
+    }
+}
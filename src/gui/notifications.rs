@@ -0,0 +1,255 @@
+use crate::core::logs::{ERROR_COLOR, Severity, WARN_COLOR};
+use crate::error::SpearError;
+
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::{Duration, Instant};
+
+/// Older toasts are dropped once this many are on screen.
+const MAX_TOASTS: usize = 5;
+const TOAST_WIDTH: f32 = 320.0;
+const TOAST_MARGIN: f32 = 10.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub severity: Severity,
+    pub title: String,
+    /// Shown behind a "Details" expander.
+    pub details: Option<String>,
+}
+
+lazy_static! {
+    static ref NOTIFICATIONS: (Mutex<Sender<Notification>>, Mutex<Receiver<Notification>>) = {
+        let (tx, rx) = channel();
+        (Mutex::new(tx), Mutex::new(rx))
+    };
+    static ref TOASTS: Mutex<ToastQueue> = Mutex::new(ToastQueue::default());
+}
+
+/// Queues a toast on the overlay. Safe to call from any thread; toasts sent
+/// before the overlay is up are shown once it is.
+pub fn notify(severity: Severity, title: impl Into<String>, details: Option<String>) {
+    let _ = NOTIFICATIONS.0.lock().unwrap().send(Notification {
+        severity,
+        title: title.into(),
+        details,
+    });
+}
+
+pub fn notify_error(title: impl Into<String>, error: &SpearError) {
+    notify(Severity::Error, title, Some(error.to_string()));
+}
+
+/// How long a toast stays up when nobody expands it.
+fn lifetime(severity: Severity) -> Duration {
+    match severity {
+        Severity::Debug | Severity::Info => Duration::from_secs(5),
+        Severity::Warn => Duration::from_secs(10),
+        Severity::Error => Duration::from_secs(20),
+    }
+}
+
+pub struct Toast {
+    pub id: u64,
+    pub notification: Notification,
+    pub shown_at: Instant,
+    /// Expanded toasts stay until dismissed.
+    pub expanded: bool,
+}
+
+#[derive(Default)]
+pub struct ToastQueue {
+    toasts: VecDeque<Toast>,
+    next_id: u64,
+}
+
+impl ToastQueue {
+    /// Adds `notification`, or restarts the timer of an identical toast
+    /// already on screen so repeated failures don't stack up.
+    pub fn push(&mut self, notification: Notification, now: Instant) {
+        if let Some(toast) = self
+            .toasts
+            .iter_mut()
+            .find(|t| t.notification == notification)
+        {
+            toast.shown_at = now;
+            return;
+        }
+        self.toasts.push_back(Toast {
+            id: self.next_id,
+            notification,
+            shown_at: now,
+            expanded: false,
+        });
+        self.next_id += 1;
+        while self.toasts.len() > MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+    }
+
+    pub fn expire(&mut self, now: Instant) {
+        self.toasts.retain(|t| {
+            t.expanded || now.duration_since(t.shown_at) < lifetime(t.notification.severity)
+        });
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.toasts.retain(|t| t.id != id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Toast> {
+        self.toasts.iter_mut()
+    }
+}
+
+fn severity_color(severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Debug | Severity::Info => egui::Color32::from_rgb(120, 170, 230),
+        Severity::Warn => WARN_COLOR,
+        Severity::Error => ERROR_COLOR,
+    }
+}
+
+/// Draws queued toasts in the top-right corner. Returns whether the pointer
+/// is over one, so the overlay stops passing clicks through.
+pub fn show_toasts(ctx: &egui::Context) -> bool {
+    let now = Instant::now();
+    let mut queue = TOASTS.lock().unwrap();
+    for notification in NOTIFICATIONS.1.lock().unwrap().try_iter() {
+        queue.push(notification, now);
+    }
+    queue.expire(now);
+    if queue.is_empty() {
+        return false;
+    }
+    ctx.request_repaint_after(Duration::from_millis(250));
+
+    let mut dismissed = None;
+    let area = egui::Area::new(egui::Id::new("toasts"))
+        .anchor(
+            egui::Align2::RIGHT_TOP,
+            egui::vec2(-TOAST_MARGIN, TOAST_MARGIN),
+        )
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.set_width(TOAST_WIDTH);
+            for toast in queue.iter_mut() {
+                let color = severity_color(toast.notification.severity);
+                egui::Frame::new()
+                    .fill(egui::Color32::from_rgba_unmultiplied(20, 20, 20, 235))
+                    .stroke(egui::Stroke::new(1.0, color))
+                    .inner_margin(egui::Margin::same(8))
+                    .corner_radius(egui::CornerRadius::same(4))
+                    .show(ui, |ui| {
+                        ui.set_width(TOAST_WIDTH);
+                        ui.horizontal(|ui| {
+                            ui.colored_label(color, toast.notification.severity.label());
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if ui.small_button("x").clicked() {
+                                        dismissed = Some(toast.id);
+                                    }
+                                },
+                            );
+                        });
+                        ui.label(
+                            egui::RichText::new(&toast.notification.title)
+                                .color(egui::Color32::WHITE),
+                        );
+                        if let Some(details) = &toast.notification.details {
+                            let header = egui::CollapsingHeader::new("Details")
+                                .id_salt(("toast", toast.id))
+                                .show(ui, |ui| {
+                                    ui.label(details);
+                                });
+                            toast.expanded = header.openness > 0.0;
+                        }
+                    });
+                ui.add_space(6.0);
+            }
+        });
+    if let Some(id) = dismissed {
+        queue.dismiss(id);
+    }
+    area.response.contains_pointer()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(severity: Severity, title: &str) -> Notification {
+        Notification {
+            severity,
+            title: title.to_string(),
+            details: None,
+        }
+    }
+
+    fn titles(queue: &mut ToastQueue) -> Vec<String> {
+        queue
+            .iter_mut()
+            .map(|t| t.notification.title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn toasts_expire_by_severity() {
+        let t0 = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push(notification(Severity::Info, "info"), t0);
+        queue.push(notification(Severity::Error, "error"), t0);
+        queue.expire(t0 + Duration::from_secs(6));
+        assert_eq!(titles(&mut queue), vec!["error"]);
+        queue.expire(t0 + Duration::from_secs(21));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn expanded_toasts_stay_until_dismissed() {
+        let t0 = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push(notification(Severity::Info, "info"), t0);
+        let id = queue.iter_mut().next().map(|t| {
+            t.expanded = true;
+            t.id
+        });
+        queue.expire(t0 + Duration::from_secs(60));
+        assert!(!queue.is_empty());
+        queue.dismiss(id.unwrap());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn repeats_refresh_instead_of_stacking() {
+        let t0 = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push(notification(Severity::Info, "same"), t0);
+        queue.push(
+            notification(Severity::Info, "same"),
+            t0 + Duration::from_secs(4),
+        );
+        assert_eq!(titles(&mut queue), vec!["same"]);
+        queue.expire(t0 + Duration::from_secs(6));
+        assert!(!queue.is_empty());
+    }
+
+    #[test]
+    fn oldest_toasts_are_dropped() {
+        let t0 = Instant::now();
+        let mut queue = ToastQueue::default();
+        for i in 0..MAX_TOASTS + 2 {
+            queue.push(notification(Severity::Info, &i.to_string()), t0);
+        }
+        let shown = titles(&mut queue);
+        assert_eq!(shown.len(), MAX_TOASTS);
+        assert_eq!(shown[0], "2");
+    }
+}
@@ -1,6 +1,9 @@
 
+use crate::config::layout::{LAYOUT_CHANGED, layout_spec};
+use crate::gui::layout::{Layout, scale_for_dpi};
 use crate::gui::overlay_ui::*;
-use crate::gui::settings::show_settings_window;
+use crate::gui::overlay_utils::{client_size, window_dpi};
+use crate::gui::settings::open_settings_window;
 
 use egui;
 use lazy_static::lazy_static;
@@ -11,9 +14,53 @@ lazy_static! {
     pub static ref INPUT_NEEDED: Mutex<bool> = Mutex::new(false);
 }
 
+/// Lays the overlay out again and resizes it to the launcher's client area
+/// when that area or its DPI changed, e.g. after the launcher was dragged to
+/// a monitor with different scaling, or when `layout.toml` was edited.
+fn relayout(
+    main_hwnd: winapi::shared::windef::HWND,
+    overlay_hwnd: winapi::shared::windef::HWND,
+    force: bool,
+) {
+    // A minimized launcher has an empty client area; keep the last layout.
+    let Some(client) = client_size(main_hwnd).filter(|c| c.x > 0.0 && c.y > 0.0) else {
+        return;
+    };
+    let scale = scale_for_dpi(window_dpi(main_hwnd));
+    {
+        let mut layout = LAYOUT.lock().unwrap();
+        if !force && !layout.is_stale(client, scale) {
+            return;
+        }
+        log::info!(
+            "[+] Laying out overlay for {}x{} at {}% scaling",
+            client.x,
+            client.y,
+            (scale * 100.0).round()
+        );
+        *layout = Layout::compute(&layout_spec(), client, scale);
+    }
+    unsafe {
+        winapi::um::winuser::SetWindowPos(
+            overlay_hwnd,
+            std::ptr::null_mut(),
+            0,
+            0,
+            client.x as i32,
+            client.y as i32,
+            winapi::um::winuser::SWP_NOZORDER | winapi::um::winuser::SWP_NOACTIVATE,
+        );
+        let hrgn = winapi::um::wingdi::CreateRectRgn(0, 0, client.x as i32, client.y as i32);
+        winapi::um::winuser::SetWindowRgn(overlay_hwnd, hrgn, 1);
+    }
+    // Text textures are rasterized for one scale and font.
+    *TEXT_TEXTURE.lock().unwrap() = None;
+    *PEACOCK_VERSION_TEXTURE.lock().unwrap() = None;
+}
+
 pub fn update(egui_ctx: &egui::Context, overlay_hwnd: winapi::shared::windef::HWND) {
+    let main_hwnd = (*MAIN_HWND.lock().unwrap()).map(|h| h as winapi::shared::windef::HWND);
     {
-        let main_hwnd = (*MAIN_HWND.lock().unwrap()).map(|h| h as winapi::shared::windef::HWND);
         let mut set_parent_done = SET_PARENT_DONE.lock().unwrap();
         if !*set_parent_done {
             if let Some(main_hwnd) = main_hwnd {
@@ -41,15 +88,6 @@ pub fn update(egui_ctx: &egui::Context, overlay_hwnd: winapi::shared::windef::HW
                             std::mem::size_of::<u32>() as u32,
                         );
                     }
-                    let hrgn = unsafe {
-                        winapi::um::wingdi::CreateRectRgn(
-                            0,
-                            0,
-                            *WIDTH.lock().unwrap() as i32,
-                            *HEIGHT.lock().unwrap() as i32,
-                        )
-                    };
-                    unsafe { winapi::um::winuser::SetWindowRgn(overlay_hwnd, hrgn, 1) };
                     let ex_style = unsafe {
                         winapi::um::winuser::GetWindowLongPtrA(
                             overlay_hwnd,
@@ -71,20 +109,13 @@ pub fn update(egui_ctx: &egui::Context, overlay_hwnd: winapi::shared::windef::HW
                             winapi::um::winuser::LWA_ALPHA,
                         )
                     };
-                    unsafe {
-                        winapi::um::winuser::SetWindowPos(
-                            overlay_hwnd,
-                            std::ptr::null_mut(),
-                            0,
-                            0,
-                            0,
-                            0,
-                            winapi::um::winuser::SWP_NOSIZE | winapi::um::winuser::SWP_NOZORDER,
-                        );
-                    }
+                    relayout(main_hwnd, overlay_hwnd, true);
                     *set_parent_done = true;
                 }
             }
+        } else if let Some(main_hwnd) = main_hwnd {
+            let spec_changed = std::mem::take(&mut *LAYOUT_CHANGED.lock().unwrap());
+            relayout(main_hwnd, overlay_hwnd, spec_changed);
         }
     }
 
@@ -92,12 +123,8 @@ pub fn update(egui_ctx: &egui::Context, overlay_hwnd: winapi::shared::windef::HW
 
     if *crate::gui::overlay_ui::SHOULD_SPAWN_EXTRA.lock().unwrap()
         && *crate::gui::overlay_ui::UI_READY.lock().unwrap()
-        && !*crate::gui::overlay_ui::EXTRA_SPAWNED.lock().unwrap()
     {
-        std::thread::spawn(|| {
-            show_settings_window();
-        });
-        *crate::gui::overlay_ui::EXTRA_SPAWNED.lock().unwrap() = true;
+        open_settings_window();
         *crate::gui::overlay_ui::SHOULD_SPAWN_EXTRA.lock().unwrap() = false;
     }
 }
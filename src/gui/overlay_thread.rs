@@ -1,4 +1,5 @@
 use crate::constants::*;
+use crate::error::Context;
 use crate::gui::overlay_ui::*;
 
 use egui_overlay;
@@ -6,7 +7,6 @@ use egui_overlay::EguiOverlay;
 use egui_overlay::egui_render_three_d::ThreeDBackend;
 use egui_overlay::egui_window_glfw_passthrough::{GlfwBackend, GlfwConfig};
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
-use tokio;
 
 struct Overlay;
 
@@ -26,6 +26,12 @@ impl EguiOverlay for Overlay {
         } else {
             std::ptr::null_mut()
         };
+        if let Some(main_hwnd) = *MAIN_HWND.lock().unwrap() {
+            crate::gui::navigation::poll(
+                &glfw_backend.glfw,
+                main_hwnd as winapi::shared::windef::HWND,
+            );
+        }
         crate::gui::overlay::update(egui_context, overlay_hwnd);
         let input_needed = *crate::gui::overlay::INPUT_NEEDED.lock().unwrap();
         if input_needed {
@@ -52,27 +58,11 @@ pub unsafe fn overlay_thread_func(
 ) -> winapi::shared::minwindef::DWORD {
     log::info!("[+] Overlay thread started");
 
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    let path = SPEAR_PATH.join("peacock").join("chunk0.js");
-    if !path.exists() {
-        std::fs::create_dir_all(SPEAR_PATH.join("peacock")).unwrap();
-        let version = rt
-            .block_on(crate::core::resources::peacock_download_release())
-            .unwrap_or_else(|e| {
-                log::error!("Failed to download peacock: {}", e);
-                "unknown".to_string()
-            });
-        *crate::gui::overlay_ui::PEACOCK_VERSION.lock().unwrap() =
-            Some(version.trim_start_matches('v').to_string());
-    } else {
-        let version_path = SPEAR_PATH.join("peacock").join("version.txt");
-        if let Ok(version) = std::fs::read_to_string(version_path) {
-            *crate::gui::overlay_ui::PEACOCK_VERSION.lock().unwrap() =
-                Some(version.trim().trim_start_matches('v').to_string());
-        } else {
-            *crate::gui::overlay_ui::PEACOCK_VERSION.lock().unwrap() = Some("unknown".to_string());
-        }
-    }
+    // Downloads in the background; PLAY stays disabled until it's done.
+    let force_reinstall = crate::config::runtime::resolve_runtime_settings()
+        .settings
+        .force_reinstall;
+    crate::core::install::spawn_install(force_reinstall);
 
     log::info!("[+] Setting up overlay...");
 
@@ -89,25 +79,37 @@ pub unsafe fn overlay_thread_func(
     log::info!("[+] Checking for cached resources...");
     if icon_path.exists() {
         log::info!("[+] Loading icon from cache...");
-        let data = std::fs::read(icon_path).unwrap();
-        *crate::core::resources::PLAY_ICON_DATA.lock().unwrap() = Some(data);
-        log::info!("[+] Loaded icon from cache");
+        match std::fs::read(icon_path) {
+            Ok(data) => {
+                *crate::core::resources::PLAY_ICON_DATA.lock().unwrap() = Some(data);
+                log::info!("[+] Loaded icon from cache");
+            }
+            Err(e) => log::error!("[!] Failed to read cached icon: {}", e),
+        }
     } else {
         log::info!("[+] Icon not cached");
     }
     if settings_icon_path.exists() {
         log::info!("[+] Loading settings icon from cache...");
-        let data = std::fs::read(settings_icon_path).unwrap();
-        *crate::core::resources::SETTINGS_ICON_DATA.lock().unwrap() = Some(data);
-        log::info!("[+] Loaded settings icon from cache");
+        match std::fs::read(settings_icon_path) {
+            Ok(data) => {
+                *crate::core::resources::SETTINGS_ICON_DATA.lock().unwrap() = Some(data);
+                log::info!("[+] Loaded settings icon from cache");
+            }
+            Err(e) => log::error!("[!] Failed to read cached settings icon: {}", e),
+        }
     } else {
         log::info!("[+] Settings icon not cached");
     }
     if font_path.exists() {
         log::info!("[+] Loading font from cache...");
-        let data = std::fs::read(font_path).unwrap();
-        *crate::core::resources::FONT_DATA.lock().unwrap() = Some(data);
-        log::info!("[+] Loaded font from cache");
+        match std::fs::read(font_path) {
+            Ok(data) => {
+                *crate::core::resources::FONT_DATA.lock().unwrap() = Some(data);
+                log::info!("[+] Loaded font from cache");
+            }
+            Err(e) => log::error!("[!] Failed to read cached font: {}", e),
+        }
     } else {
         log::info!("[+] Font not cached");
     }
@@ -122,30 +124,29 @@ pub unsafe fn overlay_thread_func(
             .is_none()
         || crate::core::resources::FONT_DATA.lock().unwrap().is_none()
     {
-        let exe_path = std::env::current_exe().unwrap();
-        crate::pe::parsing::parse_pe_resources(&exe_path);
+        if let Err(e) = std::env::current_exe()
+            .context("Failed to locate the launcher executable")
+            .and_then(|exe_path| crate::pe::parsing::parse_pe_resources(&exe_path))
+        {
+            log::error!("[!] {}", e);
+        }
     } else {
         log::info!("[+] All resources loaded from cache");
     }
 
     let main_hwnd = unsafe { crate::gui::find_main_window() };
     if let Some(main_hwnd) = main_hwnd {
-        let mut main_rect = unsafe { std::mem::zeroed() };
-        unsafe { winapi::um::winuser::GetWindowRect(main_hwnd, &mut main_rect) };
-        let width = (main_rect.right - main_rect.left) as f32;
-        let height = (main_rect.bottom - main_rect.top) as f32;
-        *WIDTH.lock().unwrap() = width;
-        *HEIGHT.lock().unwrap() = height;
+        // The layout itself follows the client area once the overlay is parented.
         *MAIN_HWND.lock().unwrap() = Some(main_hwnd as usize);
         log::info!(
-            "[+] Main window position: ({}, {}), size: {}x{}",
-            main_rect.left,
-            main_rect.top,
-            width,
-            height
+            "[+] Main window client size: {:?}, DPI: {}",
+            crate::gui::overlay_utils::client_size(main_hwnd),
+            crate::gui::overlay_utils::window_dpi(main_hwnd)
         );
     }
 
+    crate::config::layout::reload_layout();
+
     log::info!("[+] Starting egui_overlay...");
     let config = GlfwConfig {
         glfw_callback: Box::new(|gtx| {
@@ -155,6 +156,7 @@ pub unsafe fn overlay_thread_func(
             );
         }),
         transparent_window: Some(true),
+        // Work in physical pixels; `gui::layout` applies the launcher's DPI.
         disable_content_scaling: true,
         ..Default::default()
     };
@@ -1,7 +1,13 @@
-use crate::core::injection::perform_injection;
+use crate::config::profiles::{ACTIVE_PROFILE, cycle_active_profile};
+use crate::core::injection::{perform_injection, wait_for_game_exit};
+use crate::core::install::{InstallState, install_state, play_allowed, spawn_install};
+use crate::core::logs::ERROR_COLOR;
 use crate::core::resources::*;
-use crate::gui::debug::DEBUG_PARAMS;
+use crate::error::{Context, SpearResult};
 use crate::gui::gdi::text_rendering;
+use crate::gui::layout::Layout;
+use crate::gui::navigation::{self, NavAction, NavTarget};
+use crate::gui::notifications::{notify_error, show_toasts};
 
 use egui;
 use lazy_static::lazy_static;
@@ -14,10 +20,11 @@ lazy_static! {
     pub static ref TEXT_SIZE: Mutex<egui::Vec2> = Mutex::new(egui::Vec2::ZERO);
     pub static ref MAIN_HWND: Mutex<Option<usize>> = Mutex::new(None);
     pub static ref SET_PARENT_DONE: Mutex<bool> = Mutex::new(false);
-    pub static ref WIDTH: Mutex<f32> = Mutex::new(1920.0);
-    pub static ref HEIGHT: Mutex<f32> = Mutex::new(1080.0);
+    /// Geometry for the launcher's current client size and DPI.
+    pub static ref LAYOUT: Mutex<Layout> = Mutex::new(Layout::default());
     pub static ref UI_READY: Mutex<bool> = Mutex::new(false);
     pub static ref SHOULD_SPAWN_EXTRA: Mutex<bool> = Mutex::new(false);
+    /// Whether the settings window is currently shown.
     pub static ref EXTRA_SPAWNED: Mutex<bool> = Mutex::new(false);
     pub static ref PLAY_BUTTON_DISABLED: Mutex<bool> = Mutex::new(false);
     pub static ref PEACOCK_VERSION_TEXTURE: Mutex<Option<egui::TextureHandle>> = Mutex::new(None);
@@ -25,6 +32,92 @@ lazy_static! {
     pub static ref PEACOCK_VERSION: Mutex<Option<String>> = Mutex::new(None);
 }
 
+/// Publishes the installed Peacock version, re-rendering its label.
+pub fn set_peacock_version(version: Option<String>) {
+    *PEACOCK_VERSION.lock().unwrap() = version;
+    *PEACOCK_VERSION_TEXTURE.lock().unwrap() = None;
+}
+
+/// Draws the slim install progress bar in the bottom-right corner until
+/// Peacock is ready. Returns whether the pointer is over its label, which
+/// retries a failed download when clicked.
+fn show_install_progress(ui: &mut egui::Ui, layout: &Layout) -> bool {
+    let state = install_state();
+    let Some(progress) = state.progress() else {
+        return false;
+    };
+    let failed = matches!(state, InstallState::Failed(_));
+    let bar_rect = layout.progress_bar;
+    ui.painter().rect_filled(
+        bar_rect,
+        egui::CornerRadius::ZERO,
+        egui::Color32::from_rgba_unmultiplied(255, 255, 255, 40),
+    );
+    let mut filled = bar_rect;
+    filled.set_width(bar_rect.width() * progress);
+    ui.painter().rect_filled(
+        filled,
+        egui::CornerRadius::ZERO,
+        if failed {
+            ERROR_COLOR
+        } else {
+            egui::Color32::WHITE
+        },
+    );
+
+    let label_rect = egui::Rect::from_min_max(
+        egui::pos2(bar_rect.left(), bar_rect.top() - layout.px(18.0)),
+        egui::pos2(bar_rect.right(), bar_rect.top() - layout.px(2.0)),
+    );
+    let sense = if failed {
+        egui::Sense::click()
+    } else {
+        egui::Sense::hover()
+    };
+    let response = ui.allocate_rect(label_rect, sense);
+    let hovered = failed && response.hovered();
+    ui.painter().text(
+        label_rect.right_center(),
+        egui::Align2::RIGHT_CENTER,
+        state.label(),
+        egui::FontId::proportional(layout.px(11.0)),
+        if hovered {
+            egui::Color32::from_rgb(138, 138, 138)
+        } else {
+            egui::Color32::WHITE
+        },
+    );
+    if failed && response.clicked() {
+        spawn_install(true);
+    }
+    ui.ctx()
+        .request_repaint_after(std::time::Duration::from_millis(100));
+    hovered
+}
+
+/// Outline around the button that has keyboard/controller focus.
+fn draw_focus_ring(ui: &egui::Ui, rect: egui::Rect, layout: &Layout) {
+    ui.painter().rect_stroke(
+        rect.expand(layout.px(2.0)),
+        egui::CornerRadius::ZERO,
+        egui::Stroke::new(layout.px(2.0), egui::Color32::WHITE),
+        egui::StrokeKind::Outside,
+    );
+}
+
+fn load_texture(
+    egui_ctx: &egui::Context,
+    name: &str,
+    data: &[u8],
+) -> SpearResult<egui::TextureHandle> {
+    let img = image::load_from_memory(data)
+        .context(format!("Failed to decode {}", name))?
+        .to_rgba8();
+    let size = [img.width() as usize, img.height() as usize];
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &img.into_raw());
+    Ok(egui_ctx.load_texture(name, color_image, egui::TextureOptions::default()))
+}
+
 pub fn render_overlay_ui(egui_ctx: &egui::Context) {
     let mut visuals = egui::Visuals::default();
     visuals.clip_rect_margin = 0.0;
@@ -37,17 +130,20 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
     {
         let mut icon_texture = ICON_TEXTURE.lock().unwrap();
         if icon_texture.is_none() {
-            if let Some(data) = &*PLAY_ICON_DATA.lock().unwrap() {
+            let mut data = PLAY_ICON_DATA.lock().unwrap();
+            if let Some(bytes) = &*data {
                 log::info!("[+] Loading play icon texture...");
-                let img = image::load_from_memory(data).unwrap().to_rgba8();
-                let size = [img.width() as usize, img.height() as usize];
-                let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &img.into_raw());
-                *icon_texture = Some(egui_ctx.load_texture(
-                    "play_icon",
-                    color_image,
-                    egui::TextureOptions::default(),
-                ));
-                log::info!("[+] Play icon texture loaded");
+                match load_texture(egui_ctx, "play_icon", bytes) {
+                    Ok(texture) => {
+                        *icon_texture = Some(texture);
+                        log::info!("[+] Play icon texture loaded");
+                    }
+                    Err(e) => {
+                        // Drop the bad data so this isn't retried every frame.
+                        log::error!("[!] {}", e);
+                        *data = None;
+                    }
+                }
             } else {
                 log::info!("[!] Play icon data not available");
             }
@@ -57,63 +153,59 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
     {
         let mut settings_icon_texture = SETTINGS_ICON_TEXTURE.lock().unwrap();
         if settings_icon_texture.is_none() {
-            if let Some(data) = &*SETTINGS_ICON_DATA.lock().unwrap() {
+            let mut data = SETTINGS_ICON_DATA.lock().unwrap();
+            if let Some(bytes) = &*data {
                 log::info!("[+] Loading settings icon texture...");
-                let img = image::load_from_memory(data).unwrap().to_rgba8();
-                let size = [img.width() as usize, img.height() as usize];
-                let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &img.into_raw());
-                *settings_icon_texture = Some(egui_ctx.load_texture(
-                    "settings_icon",
-                    color_image,
-                    egui::TextureOptions::default(),
-                ));
-                log::info!("[+] Settings icon texture loaded");
+                match load_texture(egui_ctx, "settings_icon", bytes) {
+                    Ok(texture) => {
+                        *settings_icon_texture = Some(texture);
+                        log::info!("[+] Settings icon texture loaded");
+                    }
+                    Err(e) => {
+                        // Drop the bad data so this isn't retried every frame.
+                        log::error!("[!] {}", e);
+                        *data = None;
+                    }
+                }
             } else {
                 log::info!("[!] Settings icon data not available");
             }
         }
     }
 
+    let layout = LAYOUT.lock().unwrap().clone();
+
     {
-        let params = DEBUG_PARAMS.lock().unwrap();
-        let needs_rerender = params.changed;
-        if TEXT_TEXTURE.lock().unwrap().is_none() || needs_rerender {
+        let mut text_texture = TEXT_TEXTURE.lock().unwrap();
+        if text_texture.is_none() {
             let (tex, size) = text_rendering::render_text_texture(
                 egui_ctx,
                 "PLAY (PEACOCK)",
-                params.font_height,
-                params.font_weight,
-                params.text_width,
-                params.text_height,
+                layout.play_text_font.height,
+                layout.play_text_font.weight,
+                layout.play_text.width(),
+                layout.play_text.height(),
             );
-            *TEXT_TEXTURE.lock().unwrap() = tex;
+            *text_texture = tex;
             *TEXT_SIZE.lock().unwrap() = size;
-            drop(params);
-            let mut params_mut = DEBUG_PARAMS.lock().unwrap();
-            params_mut.changed = false;
         }
     }
 
     {
         let version = PEACOCK_VERSION.lock().unwrap().clone();
         if let Some(version) = version {
-            let params = DEBUG_PARAMS.lock().unwrap();
-            let needs_rerender = params.version_changed;
             let mut version_texture = PEACOCK_VERSION_TEXTURE.lock().unwrap();
-            if version_texture.is_none() || needs_rerender {
+            if version_texture.is_none() {
                 let (tex, size) = text_rendering::render_text_texture(
                     egui_ctx,
                     &version,
-                    params.version_font_height,
-                    params.version_font_weight,
-                    params.version_text_width,
-                    params.version_text_height,
+                    layout.version_font.height,
+                    layout.version_font.weight,
+                    layout.version.width(),
+                    layout.version.height(),
                 );
                 *version_texture = tex;
                 *PEACOCK_VERSION_SIZE.lock().unwrap() = size;
-                drop(params);
-                let mut params_mut = DEBUG_PARAMS.lock().unwrap();
-                params_mut.version_changed = false;
             }
         }
     }
@@ -127,6 +219,9 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
         }
     }
 
+    let focus = navigation::focus();
+    let nav_action = navigation::take_action();
+
     egui::CentralPanel::default()
         .frame(egui::Frame {
             inner_margin: egui::Margin::ZERO,
@@ -136,10 +231,7 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
             ..Default::default()
         })
         .show(egui_ctx, |ui| {
-            let button_rect = egui::Rect::from_min_size(
-                egui::pos2(9.0, *HEIGHT.lock().unwrap() - 35.0),
-                egui::vec2(25.0, 25.0),
-            );
+            let button_rect = layout.settings_button;
             let mut icon_hovered = false;
             if let Some(icon_tex) = &*SETTINGS_ICON_TEXTURE.lock().unwrap() {
                 let response = ui.allocate_rect(button_rect, egui::Sense::click());
@@ -152,32 +244,52 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
                 }
                 ui.put(
                     button_rect,
-                    egui::Image::new((icon_tex.id(), egui::vec2(25.0, 25.0))),
+                    egui::Image::new((icon_tex.id(), button_rect.size())),
                 );
                 if response.clicked() {
                     *SHOULD_SPAWN_EXTRA.lock().unwrap() = true;
                 }
                 icon_hovered = response.hovered();
             }
+            // Navigation works even before the icon has loaded.
+            if focus == Some(NavTarget::Settings) {
+                draw_focus_ring(ui, button_rect, &layout);
+            }
+            if nav_action == Some(NavAction::OpenSettings) {
+                *SHOULD_SPAWN_EXTRA.lock().unwrap() = true;
+            }
 
-            let button_rect = egui::Rect::from_min_size(
-                egui::pos2(
-                    crate::constants::WINDOW_POS_X,
-                    crate::constants::WINDOW_POS_Y,
-                ),
-                egui::vec2(
-                    crate::constants::WINDOW_WIDTH,
-                    crate::constants::WINDOW_HEIGHT,
-                ),
+            let profile_rect = layout.profile;
+            let profile_response = ui.allocate_rect(profile_rect, egui::Sense::click());
+            let profile_text = format!(
+                "Profile: {}",
+                ACTIVE_PROFILE
+                    .lock()
+                    .unwrap()
+                    .as_deref()
+                    .unwrap_or("options.ini")
             );
+            ui.painter().text(
+                profile_rect.left_center(),
+                egui::Align2::LEFT_CENTER,
+                profile_text,
+                egui::FontId::proportional(layout.px(13.0)),
+                if profile_response.hovered() {
+                    egui::Color32::from_rgb(138, 138, 138)
+                } else {
+                    egui::Color32::WHITE
+                },
+            );
+            if profile_response.clicked() {
+                cycle_active_profile();
+            }
+            let profile_hovered = profile_response.hovered();
+
+            let button_rect = layout.play_button;
             let response = ui.allocate_rect(button_rect, egui::Sense::click());
             if let Some(icon_tex) = &*ICON_TEXTURE.lock().unwrap() {
-                let icon_rect = egui::Rect::from_min_size(
-                    button_rect.min
-                        + egui::vec2(crate::constants::ICON_POS_X, crate::constants::ICON_POS_Y),
-                    egui::vec2(25.0, 25.0),
-                );
-                let disabled = *PLAY_BUTTON_DISABLED.lock().unwrap();
+                let icon_rect = layout.play_icon;
+                let disabled = *PLAY_BUTTON_DISABLED.lock().unwrap() || !play_allowed();
                 let icon_tint = if disabled {
                     egui::Color32::GRAY
                 } else {
@@ -185,10 +297,10 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
                 };
                 ui.put(
                     icon_rect,
-                    egui::Image::new((icon_tex.id(), egui::vec2(25.0, 25.0))).tint(icon_tint),
+                    egui::Image::new((icon_tex.id(), icon_rect.size())).tint(icon_tint),
                 );
             }
-            let disabled = *PLAY_BUTTON_DISABLED.lock().unwrap();
+            let disabled = *PLAY_BUTTON_DISABLED.lock().unwrap() || !play_allowed();
             let text_color = if disabled {
                 egui::Color32::GRAY
             } else if response.hovered() {
@@ -197,30 +309,36 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
                 egui::Color32::WHITE
             };
             if let Some(text_tex) = &*TEXT_TEXTURE.lock().unwrap() {
-                let params = crate::gui::debug::DEBUG_PARAMS.lock().unwrap();
-                let text_rect = egui::Rect::from_min_size(
-                    button_rect.min + egui::vec2(params.text_pos_x, params.text_pos_y),
-                    *TEXT_SIZE.lock().unwrap(),
-                );
+                let text_rect =
+                    egui::Rect::from_min_size(layout.play_text.min, *TEXT_SIZE.lock().unwrap());
                 ui.put(
                     text_rect,
                     egui::Image::new((text_tex.id(), *TEXT_SIZE.lock().unwrap())).tint(text_color),
                 );
             }
-            if response.clicked() {
-                if *PLAY_BUTTON_DISABLED.lock().unwrap() {
-                    return;
-                }
+            if focus == Some(NavTarget::Play) {
+                draw_focus_ring(ui, button_rect, &layout);
+            }
+            if (response.clicked() || nav_action == Some(NavAction::Play)) && !disabled {
                 *PLAY_BUTTON_DISABLED.lock().unwrap() = true;
-                perform_injection();
+                // Off the UI thread so toasts keep drawing while the game starts.
+                std::thread::spawn(|| {
+                    if let Err(e) = perform_injection() {
+                        log::error!("[!] {}", e);
+                        notify_error("Could not start the game", &e);
+                        wait_for_game_exit();
+                        *PLAY_BUTTON_DISABLED.lock().unwrap() = false;
+                    }
+                });
             }
             let play_hovered = response.hovered();
-            *crate::gui::overlay::INPUT_NEEDED.lock().unwrap() = icon_hovered || play_hovered;
+            let install_hovered = show_install_progress(ui, &layout);
+            *crate::gui::overlay::INPUT_NEEDED.lock().unwrap() =
+                icon_hovered || play_hovered || profile_hovered || install_hovered;
 
             if let Some(version_tex) = &*PEACOCK_VERSION_TEXTURE.lock().unwrap() {
-                let params = crate::gui::debug::DEBUG_PARAMS.lock().unwrap();
                 let version_rect = egui::Rect::from_min_size(
-                    egui::pos2(params.version_text_pos_x, params.version_text_pos_y),
+                    layout.version.min,
                     *PEACOCK_VERSION_SIZE.lock().unwrap(),
                 );
                 ui.put(
@@ -230,4 +348,8 @@ pub fn render_overlay_ui(egui_ctx: &egui::Context) {
                 );
             }
         });
+
+    if show_toasts(egui_ctx) {
+        *crate::gui::overlay::INPUT_NEEDED.lock().unwrap() = true;
+    }
 }
@@ -1,7 +1,9 @@
 use pelite::pe64::Pe;
 use pelite::pe64::PeView;
 use std::ptr;
-use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::shared::windef::{HWND, RECT};
+use winapi::um::libloaderapi::{GetModuleHandleA, GetModuleHandleW, GetProcAddress};
+use winapi::um::winuser::GetClientRect;
 
 pub fn capitalize_first(s: &str) -> String {
     let mut chars = s.chars();
@@ -46,3 +48,33 @@ pub fn get_text_section() -> Option<(*const u8, usize)> {
     }
     None
 }
+
+/// DPI of the monitor `hwnd` is on, or 0 before Windows 10 1607 where
+/// `GetDpiForWindow` doesn't exist.
+pub fn window_dpi(hwnd: HWND) -> u32 {
+    type GetDpiForWindow = unsafe extern "system" fn(HWND) -> u32;
+    unsafe {
+        let user32 = GetModuleHandleA(c"user32.dll".as_ptr());
+        if user32.is_null() {
+            return 0;
+        }
+        let proc = GetProcAddress(user32, c"GetDpiForWindow".as_ptr());
+        if proc.is_null() {
+            return 0;
+        }
+        let get_dpi_for_window: GetDpiForWindow = std::mem::transmute(proc);
+        get_dpi_for_window(hwnd)
+    }
+}
+
+/// Size of the client area of `hwnd` in physical pixels.
+pub fn client_size(hwnd: HWND) -> Option<egui::Vec2> {
+    let mut rect: RECT = unsafe { std::mem::zeroed() };
+    if unsafe { GetClientRect(hwnd, &mut rect) } == 0 {
+        return None;
+    }
+    Some(egui::vec2(
+        (rect.right - rect.left) as f32,
+        (rect.bottom - rect.top) as f32,
+    ))
+}
@@ -0,0 +1,99 @@
+use std::ops::Range;
+
+/// Splits a search box input into lowercase terms. All terms have to match.
+pub fn parse_query(input: &str) -> Vec<String> {
+    input.split_whitespace().map(|t| t.to_lowercase()).collect()
+}
+
+/// Returns the byte index in `text` where `term` ends if it starts at `start`,
+/// comparing case-insensitively one char at a time so offsets stay valid.
+fn match_at(text: &str, start: usize, term: &str) -> Option<usize> {
+    let mut text_chars = text[start..].char_indices();
+    for term_char in term.chars() {
+        let (_, text_char) = text_chars.next()?;
+        if !text_char.to_lowercase().eq(term_char.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(
+        text_chars
+            .next()
+            .map(|(idx, _)| start + idx)
+            .unwrap_or(text.len()),
+    )
+}
+
+fn contains_term(text: &str, term: &str) -> bool {
+    text.char_indices()
+        .any(|(idx, _)| match_at(text, idx, term).is_some())
+}
+
+/// True when every term appears in at least one of `fields`, e.g. an option's
+/// key, title, description and category.
+pub fn matches_query(terms: &[String], fields: &[&str]) -> bool {
+    terms
+        .iter()
+        .all(|term| fields.iter().any(|field| contains_term(field, term)))
+}
+
+/// Byte ranges of `text` matching any term, sorted and merged, for highlighting.
+pub fn match_ranges(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for (idx, _) in text.char_indices() {
+        for term in terms.iter().filter(|t| !t.is_empty()) {
+            if let Some(end) = match_at(text, idx, term) {
+                ranges.push(idx..end);
+            }
+        }
+    }
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(parse_query("   ").is_empty());
+        assert!(matches_query(&parse_query(""), &["liveSplit"]));
+    }
+
+    #[test]
+    fn matching_is_case_insensitive_across_fields() {
+        let fields = [
+            "liveSplit",
+            "Live Split",
+            "Enables LiveSplit support",
+            "Splitter",
+        ];
+        assert!(matches_query(&parse_query("LIVESPLIT"), &fields));
+        assert!(matches_query(&parse_query("splitter support"), &fields));
+        assert!(!matches_query(&parse_query("split elusive"), &fields));
+    }
+
+    #[test]
+    fn ranges_are_merged_and_sorted() {
+        let terms = parse_query("split live");
+        assert_eq!(match_ranges("Live Split", &terms), vec![0..4, 5..10]);
+        assert_eq!(match_ranges("liveSplit", &terms), vec![0..9]);
+        assert!(match_ranges("Jokes", &terms).is_empty());
+    }
+
+    #[test]
+    fn ranges_stay_on_char_boundaries() {
+        let terms = parse_query("é");
+        let text = "Café ÉCLAIR";
+        for range in match_ranges(text, &terms) {
+            assert_eq!(&text[range].to_lowercase(), "é");
+        }
+    }
+}
@@ -1,25 +1,565 @@
-use crate::config::peacock::{PeacockCategory, load_peacock_config, save_peacock_config};
-use crate::config::spear::{SpearConfig, load_spear_config, save_spear_config};
+use crate::config::bundle::{
+    ImportPreview, apply_import, export_bundle, preview_import, read_bundle,
+};
+use crate::config::flag_changes::FLAG_CHANGES;
+use crate::config::peacock::{
+    FLAGS_SOURCE, PeacockCategory, diff_against_defaults, get_default_value,
+    get_peacock_config_path, load_peacock_config_with_defaults, parse_peacock_defaults,
+    save_peacock_config, validate_peacock_options,
+};
+use crate::config::profiles::{
+    ACTIVE_PROFILE, delete_profile, get_profile_path, is_valid_profile_name, list_profiles,
+    load_profile, save_profile, set_active_profile,
+};
+use crate::config::runtime::{ResolvedSettings, resolve_runtime_settings};
+use crate::config::spear::{
+    CONFIG_LOAD_ERROR, SpearConfig, get_spear_config_path, load_spear_config, save_spear_config,
+    validate_spear_config,
+};
+use crate::config::watch::{
+    WatchedFile, apply_peacock_values, merge_changes, peacock_values, spear_from_values,
+    spear_values,
+};
+use crate::core::diagnostics::{DIAGNOSTICS_STATUS, spawn_diagnostics_bundle};
+use crate::core::health::{Health, PEACOCK_PORT};
+use crate::core::logs::{
+    ERROR_COLOR, LOG_BUFFER, LogLine, LogSource, LogTail, Severity, WARN_COLOR, get_logs_dir,
+};
+use crate::core::peacock_server::{
+    ServerInfo, ServerStatus, get_crash_reports_dir, peacock_server_status, pending_restart,
+    recent_server_errors, request_restart,
+};
+use crate::gui::notifications::notify;
+use crate::gui::overlay_ui::EXTRA_SPAWNED;
 use crate::gui::overlay_utils::{capitalize_first, format_option_key};
+use crate::gui::search::{match_ranges, matches_query, parse_query};
 
 use eframe::EventLoopBuilderHook;
 use egui;
 use lazy_static::lazy_static;
+use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use winit::platform::windows::EventLoopBuilderExtWindows;
 
 #[derive(Clone)]
 struct SettingsState {
     spear_config: SpearConfig,
     peacock_categories: Vec<PeacockCategory>,
-    dirty: bool,
+    peacock_defaults: Vec<PeacockCategory>,
+    loaded_profile: Option<String>,
+    new_profile_name: String,
+    import_path: String,
+    import_preview: Option<ImportPreview>,
+    bundle_status: Option<String>,
+    runtime_settings: ResolvedSettings,
+    show_only_modified: bool,
+    search: String,
+    save_error: Option<String>,
+    confirm_close: bool,
+    title_unsaved: bool,
+    /// Values as last loaded from or written to disk, for three-way merges.
+    spear_base: SpearConfig,
+    peacock_base: Vec<PeacockCategory>,
+    spear_file: WatchedFile,
+    peacock_file: WatchedFile,
+    external_change: Option<ExternalChange>,
+    reload_notice: Option<String>,
+    last_poll: Instant,
+    server_status: ServerStatus,
+    server_errors: Vec<String>,
+    tab: SettingsTab,
+    log_level: Severity,
+    log_source: Option<LogSource>,
+    log_search: String,
+    log_follow: bool,
+    game_log: LogTail,
+    peacock_log: LogTail,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsTab {
+    Settings,
+    Logs,
+}
+
+/// Values another program wrote while the window had unsaved edits.
+#[derive(Clone)]
+struct ExternalChange {
+    spear_config: Option<SpearConfig>,
+    peacock_categories: Option<Vec<PeacockCategory>>,
+    conflicts: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum ExternalResolution {
+    Reload,
+    Merge,
+    KeepMine,
+}
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const SERVER_ERROR_LINES: usize = 20;
+
+/// The eframe event loop can only be created once per process, so the window
+/// is started on first use and hidden instead of destroyed when closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SettingsWindow {
+    NotStarted,
+    Starting,
+    Running,
+    /// The event loop ended, e.g. after an error, and can't be started again.
+    Exited,
 }
 
 lazy_static! {
     static ref STATE: Mutex<Option<SettingsState>> = Mutex::new(None);
+    static ref WINDOW: Mutex<SettingsWindow> = Mutex::new(SettingsWindow::NotStarted);
+    static ref SETTINGS_HWND: Mutex<Option<usize>> = Mutex::new(None);
+    static ref SETTINGS_CTX: Mutex<Option<egui::Context>> = Mutex::new(None);
 }
 
-pub fn show_settings_window() {
+/// Opens the settings window, or brings it to the front if it already exists.
+pub fn open_settings_window() {
+    let mut window = WINDOW.lock().unwrap();
+    match *window {
+        SettingsWindow::NotStarted => {
+            log::info!("[+] Starting settings window");
+            *window = SettingsWindow::Starting;
+            std::thread::spawn(run_settings_window);
+        }
+        SettingsWindow::Starting => {}
+        SettingsWindow::Exited => {
+            log::error!("[!] Settings window can't be reopened after it exited");
+            notify(
+                Severity::Error,
+                "The settings window stopped and can't be reopened",
+                Some("Restart the launcher to open the settings again.".to_string()),
+            );
+            return;
+        }
+        SettingsWindow::Running => {
+            if let Some(hwnd) = *SETTINGS_HWND.lock().unwrap() {
+                let hwnd = hwnd as winapi::shared::windef::HWND;
+                unsafe {
+                    if winapi::um::winuser::IsIconic(hwnd) != 0 {
+                        winapi::um::winuser::ShowWindow(hwnd, winapi::um::winuser::SW_RESTORE);
+                    } else {
+                        winapi::um::winuser::ShowWindow(hwnd, winapi::um::winuser::SW_SHOW);
+                    }
+                    winapi::um::winuser::SetForegroundWindow(hwnd);
+                }
+            }
+            if let Some(ctx) = &*SETTINGS_CTX.lock().unwrap() {
+                ctx.request_repaint();
+            }
+        }
+    }
+    *EXTRA_SPAWNED.lock().unwrap() = true;
+}
+
+fn hide_settings_window() {
+    log::info!("[+] Hiding settings window");
+    if let Some(hwnd) = *SETTINGS_HWND.lock().unwrap() {
+        unsafe {
+            winapi::um::winuser::ShowWindow(
+                hwnd as winapi::shared::windef::HWND,
+                winapi::um::winuser::SW_HIDE,
+            );
+        }
+    }
+    *EXTRA_SPAWNED.lock().unwrap() = false;
+}
+
+fn peacock_source_path(profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => get_profile_path(name),
+        None => get_peacock_config_path(),
+    }
+}
+
+fn load_peacock_source(
+    profile: Option<&str>,
+    defaults: &[PeacockCategory],
+) -> Vec<PeacockCategory> {
+    match profile {
+        Some(name) => load_profile(name, defaults),
+        None => load_peacock_config_with_defaults(defaults),
+    }
+}
+
+/// Points the Peacock watcher at the file the current values came from.
+fn track_peacock_source(state: &mut SettingsState) {
+    state.peacock_file = WatchedFile::new(peacock_source_path(state.loaded_profile.as_deref()));
+    state.peacock_base = state.peacock_categories.clone();
+    state.spear_config.active_profile = state.loaded_profile.clone();
+    state.spear_base.active_profile = state.loaded_profile.clone();
+}
+
+fn read_external_change(
+    state: &SettingsState,
+    spear_changed: bool,
+    peacock_changed: bool,
+) -> ExternalChange {
+    ExternalChange {
+        spear_config: spear_changed.then(load_spear_config),
+        peacock_categories: peacock_changed
+            .then(|| load_peacock_source(state.loaded_profile.as_deref(), &state.peacock_defaults)),
+        conflicts: vec![],
+    }
+}
+
+fn find_conflicts(state: &SettingsState, change: &ExternalChange) -> Vec<String> {
+    let mut conflicts = vec![];
+    if let Some(external) = &change.spear_config {
+        conflicts.extend(
+            merge_changes(
+                &spear_values(&state.spear_base),
+                &spear_values(&state.spear_config),
+                &spear_values(external),
+            )
+            .conflicts,
+        );
+    }
+    if let Some(external) = &change.peacock_categories {
+        conflicts.extend(
+            merge_changes(
+                &peacock_values(&state.peacock_base),
+                &peacock_values(&state.peacock_categories),
+                &peacock_values(external),
+            )
+            .conflicts,
+        );
+    }
+    conflicts
+}
+
+/// Reads the changed files and waits for the user to pick how to resolve them.
+fn queue_external_change(state: &mut SettingsState, spear_changed: bool, peacock_changed: bool) {
+    let mut change = read_external_change(state, spear_changed, peacock_changed);
+    if let Some(pending) = state.external_change.take() {
+        change.spear_config = change.spear_config.or(pending.spear_config);
+        change.peacock_categories = change.peacock_categories.or(pending.peacock_categories);
+    }
+    change.conflicts = find_conflicts(state, &change);
+    state.external_change = Some(change);
+}
+
+fn poll_external_changes(state: &mut SettingsState) {
+    let spear_changed = state.spear_file.changed_on_disk();
+    let peacock_changed = state.peacock_file.changed_on_disk();
+    if !spear_changed && !peacock_changed {
+        return;
+    }
+    log::info!(
+        "[+] Config changed on disk (config.toml: {}, Peacock options: {})",
+        spear_changed,
+        peacock_changed
+    );
+    let mut change = read_external_change(state, spear_changed, peacock_changed);
+    // Our own writes (e.g. the overlay switching profiles) land here too.
+    if change.spear_config.as_ref() == Some(&state.spear_config) {
+        state.spear_base = change.spear_config.take().unwrap();
+        state.spear_file.mark_synced();
+    }
+    if change.peacock_categories.as_ref() == Some(&state.peacock_categories) {
+        state.peacock_base = change.peacock_categories.take().unwrap();
+        state.peacock_file.mark_synced();
+    }
+    if change.spear_config.is_none() && change.peacock_categories.is_none() {
+        return;
+    }
+
+    let unsaved =
+        state.spear_config != state.spear_base || state.peacock_categories != state.peacock_base;
+    if unsaved || state.external_change.is_some() {
+        queue_external_change(
+            state,
+            change.spear_config.is_some(),
+            change.peacock_categories.is_some(),
+        );
+    } else {
+        state.reload_notice = Some(format!(
+            "Reloaded {} after it changed on disk",
+            changed_file_names(state, &change)
+        ));
+        resolve_external_change(state, change, ExternalResolution::Reload);
+    }
+}
+
+fn changed_file_names(state: &SettingsState, change: &ExternalChange) -> String {
+    let mut names = vec![];
+    if change.spear_config.is_some() {
+        names.push("config.toml".to_string());
+    }
+    if change.peacock_categories.is_some() {
+        names.push(
+            state
+                .peacock_file
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+    }
+    names.join(" and ")
+}
+
+fn resolve_external_change(
+    state: &mut SettingsState,
+    change: ExternalChange,
+    resolution: ExternalResolution,
+) {
+    if let Some(external) = change.spear_config {
+        match resolution {
+            ExternalResolution::Reload => {
+                *ACTIVE_PROFILE.lock().unwrap() = external.active_profile.clone();
+                state.spear_config = external.clone();
+            }
+            ExternalResolution::Merge => {
+                let merged = merge_changes(
+                    &spear_values(&state.spear_base),
+                    &spear_values(&state.spear_config),
+                    &spear_values(&external),
+                )
+                .merged;
+                match spear_from_values(merged) {
+                    Ok(config) => state.spear_config = config,
+                    Err(e) => log::error!("[!] Failed to merge Spear config: {}", e),
+                }
+            }
+            ExternalResolution::KeepMine => {}
+        }
+        state.spear_base = external;
+        state.spear_file.mark_synced();
+    }
+    if let Some(external) = change.peacock_categories {
+        match resolution {
+            ExternalResolution::Reload => state.peacock_categories = external.clone(),
+            ExternalResolution::Merge => {
+                let merged = merge_changes(
+                    &peacock_values(&state.peacock_base),
+                    &peacock_values(&state.peacock_categories),
+                    &peacock_values(&external),
+                )
+                .merged;
+                apply_peacock_values(&mut state.peacock_categories, &merged);
+            }
+            ExternalResolution::KeepMine => {}
+        }
+        state.peacock_base = external;
+        state.peacock_file.mark_synced();
+    }
+}
+
+/// Runs once per `POLL_INTERVAL` whichever tab is shown.
+fn poll_status(state: &mut SettingsState) {
+    if state.last_poll.elapsed() < POLL_INTERVAL {
+        return;
+    }
+    state.last_poll = Instant::now();
+    poll_external_changes(state);
+    state.server_status = peacock_server_status();
+    let game_lines = state.game_log.poll();
+    let peacock_lines = state.peacock_log.poll();
+    {
+        let mut buffer = LOG_BUFFER.lock().unwrap();
+        for line in game_lines {
+            buffer.push(LogSource::Game, &line);
+        }
+        for line in peacock_lines {
+            buffer.push(LogSource::Peacock, &line);
+        }
+    }
+    state.server_errors = recent_server_errors(SERVER_ERROR_LINES);
+}
+
+fn severity_color(severity: Severity, text_color: egui::Color32) -> egui::Color32 {
+    match severity {
+        Severity::Debug => egui::Color32::GRAY,
+        Severity::Info => text_color,
+        Severity::Warn => WARN_COLOR,
+        Severity::Error => ERROR_COLOR,
+    }
+}
+
+fn show_logs_tab(ui: &mut egui::Ui, state: &mut SettingsState) {
+    ui.ctx().request_repaint_after(POLL_INTERVAL);
+    poll_status(state);
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("log_level")
+            .selected_text(state.log_level.label())
+            .show_ui(ui, |ui| {
+                for level in [
+                    Severity::Debug,
+                    Severity::Info,
+                    Severity::Warn,
+                    Severity::Error,
+                ] {
+                    ui.selectable_value(&mut state.log_level, level, level.label());
+                }
+            });
+        egui::ComboBox::from_id_salt("log_source")
+            .selected_text(state.log_source.map(|s| s.label()).unwrap_or("all sources"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut state.log_source, None, "all sources");
+                for source in [LogSource::Spear, LogSource::Game, LogSource::Peacock] {
+                    ui.selectable_value(&mut state.log_source, Some(source), source.label());
+                }
+            });
+        ui.add(egui::TextEdit::singleline(&mut state.log_search).hint_text("Filter logs"));
+    });
+
+    let terms = parse_query(&state.log_search);
+    let lines: Vec<LogLine> = LOG_BUFFER
+        .lock()
+        .unwrap()
+        .lines()
+        .filter(|line| {
+            line.severity >= state.log_level
+                && state.log_source.is_none_or(|s| s == line.source)
+                && matches_query(&terms, &[&line.text])
+        })
+        .cloned()
+        .collect();
+
+    ui.horizontal(|ui| {
+        if ui
+            .button("Copy")
+            .on_hover_text("Copy the lines shown below")
+            .clicked()
+        {
+            let text: Vec<String> = lines
+                .iter()
+                .map(|l| format!("[{}] {}", l.source.label(), l.text))
+                .collect();
+            ui.ctx().copy_text(text.join("\n"));
+        }
+        if ui.button("Open log folder").clicked() {
+            if let Err(e) = std::process::Command::new("explorer")
+                .arg(get_logs_dir())
+                .spawn()
+            {
+                log::error!("[!] Failed to open log folder: {}", e);
+            }
+        }
+        if ui.button("Clear").clicked() {
+            LOG_BUFFER.lock().unwrap().clear();
+        }
+        ui.checkbox(&mut state.log_follow, "Follow");
+        ui.label(format!("{} line(s)", lines.len()));
+    });
+    ui.separator();
+
+    let text_color = ui.visuals().text_color();
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::both()
+        .auto_shrink(false)
+        .stick_to_bottom(state.log_follow)
+        .show_rows(ui, row_height, lines.len(), |ui, rows| {
+            for line in &lines[rows] {
+                ui.label(
+                    egui::RichText::new(format!("[{}] {}", line.source.label(), line.text))
+                        .monospace()
+                        .color(severity_color(line.severity, text_color)),
+                );
+            }
+        });
+}
+
+fn has_unsaved_changes(state: &SettingsState) -> bool {
+    state.spear_config != state.spear_base || state.peacock_categories != state.peacock_base
+}
+
+/// Validates the draft and writes it. On failure the draft is kept and
+/// `save_error` says why.
+fn save_draft(state: &mut SettingsState) -> bool {
+    let mut errors = validate_spear_config(&state.spear_config);
+    errors.extend(validate_peacock_options(
+        &state.peacock_categories,
+        &state.peacock_defaults,
+    ));
+    if !errors.is_empty() {
+        state.save_error = Some(errors.join("\n"));
+        return false;
+    }
+
+    let spear_conflict = state.spear_file.has_conflict();
+    let peacock_conflict = state.peacock_file.has_conflict();
+    if spear_conflict || peacock_conflict {
+        log::error!("[!] Config changed on disk since it was loaded, not saving");
+        queue_external_change(state, spear_conflict, peacock_conflict);
+        state.save_error = Some("Resolve the changes made on disk before saving".to_string());
+        return false;
+    }
+
+    log::info!("[+] Saving configs");
+    state.spear_config.active_profile = ACTIVE_PROFILE.lock().unwrap().clone();
+    let result = save_spear_config(&state.spear_config)
+        .and_then(|_| save_peacock_config(&state.peacock_categories))
+        .and_then(|_| match &state.loaded_profile {
+            Some(name) => save_profile(name, &state.peacock_categories, &state.peacock_defaults),
+            None => Ok(()),
+        });
+    if let Err(e) = result {
+        log::error!("[!] {}", e);
+        state.save_error = Some(e.to_string());
+        return false;
+    }
+
+    state.spear_base = state.spear_config.clone();
+    state.peacock_base = state.peacock_categories.clone();
+    state.spear_file.mark_synced();
+    state.peacock_file.mark_synced();
+    state.runtime_settings = resolve_runtime_settings();
+    state.save_error = None;
+    true
+}
+
+fn discard_draft(state: &mut SettingsState) {
+    log::info!("[+] Discarding unsaved settings");
+    state.spear_config = state.spear_base.clone();
+    state.peacock_categories = state.peacock_base.clone();
+    state.save_error = None;
+}
+
+/// Lays out `text` with the parts matching `terms` highlighted.
+fn highlighted(
+    ui: &egui::Ui,
+    text: &str,
+    terms: &[String],
+    color: egui::Color32,
+) -> egui::text::LayoutJob {
+    let format = egui::TextFormat {
+        font_id: egui::TextStyle::Body.resolve(ui.style()),
+        color,
+        ..Default::default()
+    };
+    let highlight = egui::TextFormat {
+        background: egui::Color32::from_rgb(120, 90, 20),
+        ..format.clone()
+    };
+    let mut job = egui::text::LayoutJob::default();
+    let mut pos = 0;
+    for range in match_ranges(text, terms) {
+        job.append(&text[pos..range.start], 0.0, format.clone());
+        job.append(&text[range.clone()], 0.0, highlight.clone());
+        pos = range.end;
+    }
+    job.append(&text[pos..], 0.0, format);
+    job
+}
+
+enum CloseChoice {
+    Save,
+    Discard,
+    KeepEditing,
+}
+
+fn run_settings_window() {
     let event_loop_builder: Option<EventLoopBuilderHook> = Some(Box::new(|event_loop_builder| {
         event_loop_builder.with_any_thread(true);
     }));
@@ -28,24 +568,247 @@ pub fn show_settings_window() {
         event_loop_builder,
         ..Default::default()
     };
-    eframe::run_simple_native("Settings", options, |ctx, _frame| {
+    let result = eframe::run_simple_native("Settings", options, |ctx, frame| {
         ctx.set_visuals(egui::Visuals::dark());
+        if *WINDOW.lock().unwrap() == SettingsWindow::Starting {
+            if let Ok(window_handle) = frame.window_handle() {
+                if let RawWindowHandle::Win32(handle) = window_handle.as_raw() {
+                    *SETTINGS_HWND.lock().unwrap() = Some(handle.hwnd.get() as usize);
+                }
+            }
+            *SETTINGS_CTX.lock().unwrap() = Some(ctx.clone());
+            *WINDOW.lock().unwrap() = SettingsWindow::Running;
+        }
+        {
+            let mut state_guard = STATE.lock().unwrap();
+            if state_guard.is_none() {
+                let peacock_defaults = parse_peacock_defaults();
+                let spear_config = load_spear_config();
+                *state_guard = Some(SettingsState {
+                    spear_base: spear_config.clone(),
+                    spear_config,
+                    peacock_categories: Vec::new(),
+                    peacock_defaults,
+                    loaded_profile: None,
+                    new_profile_name: String::new(),
+                    import_path: String::new(),
+                    import_preview: None,
+                    bundle_status: None,
+                    runtime_settings: resolve_runtime_settings(),
+                    show_only_modified: false,
+                    search: String::new(),
+                    save_error: None,
+                    confirm_close: false,
+                    title_unsaved: false,
+                    peacock_base: Vec::new(),
+                    spear_file: WatchedFile::new(get_spear_config_path()),
+                    peacock_file: WatchedFile::new(get_peacock_config_path()),
+                    external_change: None,
+                    reload_notice: None,
+                    last_poll: Instant::now(),
+                    server_status: peacock_server_status(),
+                    server_errors: recent_server_errors(SERVER_ERROR_LINES),
+                    tab: SettingsTab::Settings,
+                    log_level: Severity::Info,
+                    log_source: None,
+                    log_search: String::new(),
+                    log_follow: true,
+                    game_log: LogTail::new(get_logs_dir(), "hitman3"),
+                    peacock_log: LogTail::new(get_logs_dir(), "peacock"),
+                });
+            }
+        }
+
+        let mut close_window = false;
+        if let Some(state) = STATE.lock().unwrap().as_mut() {
+            let unsaved = has_unsaved_changes(state);
+            if unsaved != state.title_unsaved {
+                state.title_unsaved = unsaved;
+                let title = if unsaved { "Settings *" } else { "Settings" };
+                ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.to_string()));
+            }
+            if ctx.input(|i| i.viewport().close_requested()) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                if unsaved {
+                    state.confirm_close = true;
+                } else {
+                    close_window = true;
+                }
+            }
+
+            if state.confirm_close {
+                let mut choice = None;
+                egui::Window::new("Unsaved changes")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                    .show(ctx, |ui| {
+                        ui.label("You have unsaved changes. Save them before closing?");
+                        ui.horizontal(|ui| {
+                            if ui.button("Save").clicked() {
+                                choice = Some(CloseChoice::Save);
+                            }
+                            if ui.button("Discard").clicked() {
+                                choice = Some(CloseChoice::Discard);
+                            }
+                            if ui.button("Keep editing").clicked() {
+                                choice = Some(CloseChoice::KeepEditing);
+                            }
+                        });
+                    });
+                match choice {
+                    Some(CloseChoice::Save) => {
+                        state.confirm_close = false;
+                        close_window = save_draft(state);
+                    }
+                    Some(CloseChoice::Discard) => {
+                        state.confirm_close = false;
+                        discard_draft(state);
+                        close_window = true;
+                    }
+                    Some(CloseChoice::KeepEditing) => state.confirm_close = false,
+                    None => {}
+                }
+            }
+
+            egui::TopBottomPanel::top("settings_tabs").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut state.tab, SettingsTab::Settings, "Settings");
+                    ui.selectable_value(&mut state.tab, SettingsTab::Logs, "Logs");
+                });
+            });
+
+            egui::TopBottomPanel::bottom("settings_actions").show(ctx, |ui| {
+                if let Some(error) = &state.save_error {
+                    ui.colored_label(ERROR_COLOR, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(unsaved, egui::Button::new("Save"))
+                        .on_hover_text("Save and close")
+                        .clicked()
+                        && save_draft(state)
+                    {
+                        close_window = true;
+                    }
+                    if ui
+                        .add_enabled(unsaved, egui::Button::new("Apply"))
+                        .clicked()
+                    {
+                        save_draft(state);
+                    }
+                    if ui
+                        .button("Cancel")
+                        .on_hover_text("Discard unsaved changes and close")
+                        .clicked()
+                    {
+                        discard_draft(state);
+                        close_window = true;
+                    }
+                    if unsaved {
+                        ui.colored_label(WARN_COLOR, "Unsaved changes");
+                    }
+                });
+            });
+        }
+        if close_window {
+            hide_settings_window();
+        }
+
+        let tab = STATE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|s| s.tab)
+            .unwrap_or(SettingsTab::Settings);
         egui::CentralPanel::default().show(ctx, |ui| {
+            if tab == SettingsTab::Logs {
+                if let Some(state) = STATE.lock().unwrap().as_mut() {
+                    show_logs_tab(ui, state);
+                }
+                return;
+            }
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.heading("Settings");
                 ui.separator();
 
-                {
-                    let mut state_guard = STATE.lock().unwrap();
-                    if state_guard.is_none() {
-                        *state_guard = Some(SettingsState {
-                            spear_config: load_spear_config(),
-                            peacock_categories: load_peacock_config(),
-                            dirty: false,
+                if let Some(state) = STATE.lock().unwrap().as_mut() {
+                    let active_profile = ACTIVE_PROFILE.lock().unwrap().clone();
+                    let profile_changed = active_profile != state.loaded_profile
+                        && state.peacock_categories == state.peacock_base;
+                    if profile_changed || state.peacock_categories.is_empty() {
+                        state.peacock_categories =
+                            load_peacock_source(active_profile.as_deref(), &state.peacock_defaults);
+                        state.loaded_profile = active_profile.clone();
+                        track_peacock_source(state);
+                    }
+
+                    ui.ctx().request_repaint_after(POLL_INTERVAL);
+                    poll_status(state);
+
+                    if let Some(error) = &*CONFIG_LOAD_ERROR.lock().unwrap() {
+                        ui.colored_label(ERROR_COLOR, error);
+                        ui.separator();
+                    }
+
+                    let mut resolution = None;
+                    if let Some(change) = &state.external_change {
+                        ui.colored_label(
+                            WARN_COLOR,
+                            format!(
+                                "{} changed on disk while you had unsaved changes.",
+                                changed_file_names(state, change)
+                            ),
+                        );
+                        if !change.conflicts.is_empty() {
+                            ui.label(format!(
+                                "Changed in both places: {}",
+                                change.conflicts.join(", ")
+                            ));
+                        }
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Reload from disk")
+                                .on_hover_text("Discard your changes")
+                                .clicked()
+                            {
+                                resolution = Some(ExternalResolution::Reload);
+                            }
+                            if ui
+                                .button("Merge")
+                                .on_hover_text(
+                                    "Keep your changes and take everything else from disk",
+                                )
+                                .clicked()
+                            {
+                                resolution = Some(ExternalResolution::Merge);
+                            }
+                            if ui
+                                .button("Keep mine")
+                                .on_hover_text("Overwrite the file with your values")
+                                .clicked()
+                            {
+                                resolution = Some(ExternalResolution::KeepMine);
+                            }
                         });
+                        ui.separator();
                     }
-                }
-                if let Some(state) = STATE.lock().unwrap().as_mut() {
+                    if let Some(resolution) = resolution {
+                        let change = state.external_change.take().unwrap();
+                        resolve_external_change(state, change, resolution);
+                    }
+                    let mut dismiss_notice = false;
+                    if let Some(notice) = &state.reload_notice {
+                        ui.horizontal(|ui| {
+                            ui.label(notice);
+                            dismiss_notice = ui.small_button("Dismiss").clicked();
+                        });
+                        ui.separator();
+                    }
+                    if dismiss_notice {
+                        state.reload_notice = None;
+                    }
+
                     ui.label("Peacock GitHub");
                     let response =
                         ui.text_edit_singleline(&mut state.spear_config.peacock_github_repo);
@@ -54,154 +817,537 @@ pub fn show_settings_window() {
                             "[+] Peacock GitHub changed to: {}",
                             state.spear_config.peacock_github_repo
                         );
-                        state.dirty = true;
                     }
 
-                    for cat in state.peacock_categories.iter_mut() {
-                        ui.separator();
-                        ui.heading(&capitalize_first(&cat.name));
-                        for opt in cat.options.iter_mut() {
-                            if let Some(poss_vals) = &opt.possible_values {
-                                ui.label(&format_option_key(&opt.key));
-                                let mut selected = opt.value.clone();
-                                egui::ComboBox::from_id_salt(&opt.key)
-                                    .selected_text(&selected)
-                                    .show_ui(ui, |ui| {
-                                        for val in poss_vals {
-                                            ui.selectable_value(
-                                                &mut selected,
-                                                val.clone(),
-                                                val.as_str(),
-                                            );
-                                        }
-                                    });
-                                if selected != opt.value {
-                                    opt.value = selected;
-                                    log::info!("[+] Option {} changed to: {}", opt.key, opt.value);
-                                    state.dirty = true;
-                                }
-                            } else if opt.value == "true" || opt.value == "false" {
-                                let mut checked = opt.value == "true";
-                                if ui
-                                    .checkbox(&mut checked, &format_option_key(&opt.key))
-                                    .changed()
-                                {
-                                    opt.value = if checked { "true" } else { "false" }.to_string();
-                                    log::info!("[+] Option {} changed to: {}", opt.key, opt.value);
-                                    state.dirty = true;
-                                }
-                            } else {
-                                ui.label(&format_option_key(&opt.key));
-                                let response = ui.text_edit_singleline(&mut opt.value);
-                                if response.changed() {
-                                    log::info!("[+] Option {} changed to: {}", opt.key, opt.value);
-                                    state.dirty = true;
-                                }
+                    let paths = &*crate::core::paths::SPEAR_PATHS;
+                    ui.label(format!(
+                        "Data directory: {} (from {})",
+                        paths.data_dir.display(),
+                        paths.source.label()
+                    ));
+                    ui.label("Custom data directory (applies after restart)");
+                    let mut data_dir = state.spear_config.data_dir.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut data_dir).changed() {
+                        state.spear_config.data_dir =
+                            Some(data_dir.trim().to_string()).filter(|d| !d.is_empty());
+                    }
+
+                    egui::CollapsingHeader::new("Effective launch settings").show(ui, |ui| {
+                        egui::Grid::new("runtime_settings").show(ui, |ui| {
+                            for (name, value, source) in &state.runtime_settings.entries {
+                                ui.label(*name);
+                                ui.label(value);
+                                ui.label(source.label());
+                                ui.end_row();
                             }
-                            ui.label(&opt.description);
+                        });
+                        for warning in &state.runtime_settings.warnings {
+                            ui.colored_label(WARN_COLOR, warning);
                         }
+                        ui.label(
+                            "Override with SPEAR_* environment variables or --spear-* arguments.",
+                        );
+                    });
+
+                    ui.separator();
+                    ui.label("Profile");
+                    let mut selected_profile = active_profile.clone();
+                    // Switching would replace the draft with the other profile's values.
+                    let can_switch = state.peacock_categories == state.peacock_base;
+                    ui.add_enabled_ui(can_switch, |ui| {
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_salt("profile")
+                                .selected_text(
+                                    selected_profile.as_deref().unwrap_or("(options.ini)"),
+                                )
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut selected_profile,
+                                        None,
+                                        "(options.ini)",
+                                    );
+                                    for profile in list_profiles() {
+                                        ui.selectable_value(
+                                            &mut selected_profile,
+                                            Some(profile.clone()),
+                                            profile,
+                                        );
+                                    }
+                                });
+                            if let Some(name) = &active_profile {
+                                if ui.button("Delete profile").clicked() {
+                                    if let Err(e) = delete_profile(name) {
+                                        log::error!("[!] {}", e);
+                                    }
+                                    selected_profile = None;
+                                }
+                            }
+                        })
+                    })
+                    .response
+                    .on_disabled_hover_text(
+                        "Save or cancel your changes before switching profiles",
+                    );
+                    if selected_profile != active_profile {
+                        set_active_profile(selected_profile);
                     }
-                    #[cfg(debug_assertions)]
-                    {
-                        ui.separator();
-                        ui.heading("Debug Parameters");
-                        let mut params = crate::gui::debug::DEBUG_PARAMS.lock().unwrap();
-                        ui.label("Font Height");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut state.new_profile_name);
+                        let name = state.new_profile_name.trim().to_string();
                         if ui
-                            .add(egui::DragValue::new(&mut params.font_height))
-                            .changed()
+                            .add_enabled(
+                                is_valid_profile_name(&name),
+                                egui::Button::new("Save as profile"),
+                            )
+                            .clicked()
                         {
-                            params.changed = true;
+                            match save_profile(
+                                &name,
+                                &state.peacock_categories,
+                                &state.peacock_defaults,
+                            ) {
+                                Ok(_) => {
+                                    set_active_profile(Some(name.clone()));
+                                    state.loaded_profile = Some(name);
+                                    state.new_profile_name.clear();
+                                    track_peacock_source(state);
+                                }
+                                Err(e) => log::error!("[!] {}", e),
+                            }
                         }
-                        ui.label("Font Weight");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.font_weight))
-                            .changed()
-                        {
-                            params.changed = true;
+                    });
+
+                    if let Some(source) = *FLAGS_SOURCE.lock().unwrap() {
+                        ui.label(format!("Flag definitions loaded from {}", source.label()));
+                    }
+
+                    let mut dismiss_changes = false;
+                    if let Some(changes) = &*FLAG_CHANGES.lock().unwrap() {
+                        ui.separator();
+                        egui::CollapsingHeader::new(format!(
+                            "What's new in settings (Peacock {} -> {})",
+                            changes.from_version, changes.to_version
+                        ))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for change in &changes.added {
+                                ui.strong(format!("New: {}", format_option_key(&change.key)));
+                                ui.label(&change.description);
+                            }
+                            for change in &changes.removed {
+                                ui.strong(format!("Removed: {}", format_option_key(&change.key)));
+                            }
+                            for change in &changes.changed {
+                                ui.strong(format!("Changed: {}", format_option_key(&change.key)));
+                                if change.old_default != change.new_default {
+                                    ui.label(format!(
+                                        "Default: {} -> {}",
+                                        change.old_default.as_deref().unwrap_or("-"),
+                                        change.new_default.as_deref().unwrap_or("-")
+                                    ));
+                                }
+                                if change.old_possible_values != change.new_possible_values {
+                                    ui.label(format!(
+                                        "Possible values: {} -> {}",
+                                        change
+                                            .old_possible_values
+                                            .as_ref()
+                                            .map(|v| v.join(", "))
+                                            .unwrap_or("any".to_string()),
+                                        change
+                                            .new_possible_values
+                                            .as_ref()
+                                            .map(|v| v.join(", "))
+                                            .unwrap_or("any".to_string())
+                                    ));
+                                }
+                            }
+                            if let Some(path) = &changes.archived_path {
+                                ui.label(format!(
+                                    "Values of removed flags were archived to {}",
+                                    path.display()
+                                ));
+                            }
+                            if ui.button("Dismiss").clicked() {
+                                dismiss_changes = true;
+                            }
+                        });
+                    }
+                    if dismiss_changes {
+                        *FLAG_CHANGES.lock().unwrap() = None;
+                    }
+
+                    let pending: HashSet<String> = match &state.server_status {
+                        ServerStatus::Running(info) => {
+                            pending_restart(&info.options, &peacock_values(&state.peacock_base))
+                                .into_iter()
+                                .collect()
                         }
-                        ui.label("Text Width");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.text_width))
-                            .changed()
+                        _ => HashSet::new(),
+                    };
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(match &state.server_status {
+                            ServerStatus::NotRunning => "Peacock server is not running".to_string(),
+                            ServerStatus::Running(info) => format!(
+                                "Peacock server is running (PID {}, {})",
+                                info.pid,
+                                info.health.unwrap_or(Health::Starting).label()
+                            ),
+                            ServerStatus::Restarting => "Peacock server is restarting...".to_string(),
+                            ServerStatus::Failed(_) => "Peacock server is down".to_string(),
+                        });
+                        // Both only come from a running watchdog, which is
+                        // what picks the request up.
+                        if matches!(
+                            state.server_status,
+                            ServerStatus::Running(_) | ServerStatus::Failed(_)
+                        ) && ui.button("Restart Peacock server").clicked()
                         {
-                            params.changed = true;
+                            match request_restart() {
+                                Ok(_) => state.server_status = ServerStatus::Restarting,
+                                Err(e) => log::error!("[!] Failed to request restart: {}", e),
+                            }
                         }
-                        ui.label("Text Height");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.text_height))
-                            .changed()
-                        {
-                            params.changed = true;
+                    });
+                    if let ServerStatus::Running(ServerInfo {
+                        health: Some(Health::TimedOut | Health::NotResponding),
+                        ..
+                    }) = &state.server_status
+                    {
+                        ui.colored_label(
+                            ERROR_COLOR,
+                            format!(
+                                "The Peacock server isn't answering on port {}, so the game \
+                                 can't connect. Check the server errors below.",
+                                PEACOCK_PORT
+                            ),
+                        );
+                    }
+                    if let ServerStatus::Failed(reason) = &state.server_status {
+                        ui.colored_label(
+                            ERROR_COLOR,
+                            format!(
+                                "{}. Crash reports are in {}",
+                                reason,
+                                get_crash_reports_dir().display()
+                            ),
+                        );
+                    }
+                    if !state.server_errors.is_empty() {
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new(format!(
+                                "Recent server errors ({})",
+                                state.server_errors.len()
+                            ))
+                            .color(ERROR_COLOR),
+                        )
+                        .show(ui, |ui| {
+                            for line in &state.server_errors {
+                                ui.monospace(line);
+                            }
+                            ui.label(format!(
+                                "Full server logs are in {}",
+                                get_logs_dir().display()
+                            ));
+                        });
+                    }
+                    if !pending.is_empty() {
+                        ui.colored_label(
+                            WARN_COLOR,
+                            format!(
+                                "{} saved change(s) take effect after restarting the Peacock server",
+                                pending.len()
+                            ),
+                        );
+                    }
+
+                    let modified: HashSet<(String, String)> =
+                        diff_against_defaults(&state.peacock_categories, &state.peacock_defaults)
+                            .into_iter()
+                            .map(|d| (d.section, d.key))
+                            .collect();
+                    ui.horizontal(|ui| {
+                        let search = ui.add(
+                            egui::TextEdit::singleline(&mut state.search)
+                                .hint_text("Search settings (Ctrl+F)"),
+                        );
+                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+                            search.request_focus();
                         }
-                        ui.label("Text Pos X");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.text_pos_x))
-                            .changed()
-                        {
-                            params.changed = true;
+                        if search.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                            state.search.clear();
                         }
-                        ui.label("Text Pos Y");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.text_pos_y))
-                            .changed()
-                        {
-                            params.changed = true;
+                        ui.checkbox(&mut state.show_only_modified, "Show only modified");
+                        ui.label(format!("{} modified", modified.len()));
+                    });
+
+                    let terms = parse_query(&state.search);
+                    let show_only_modified = state.show_only_modified;
+                    let text_color = ui.visuals().text_color();
+                    let mut any_visible = false;
+                    for cat in state.peacock_categories.iter_mut() {
+                        let visible: HashSet<String> = cat
+                            .options
+                            .iter()
+                            .filter(|o| {
+                                (!show_only_modified
+                                    || modified.contains(&(cat.name.clone(), o.key.clone())))
+                                    && matches_query(
+                                        &terms,
+                                        &[
+                                            &o.key,
+                                            &format_option_key(&o.key),
+                                            &o.description,
+                                            &o.category,
+                                            &cat.name,
+                                        ],
+                                    )
+                            })
+                            .map(|o| o.key.clone())
+                            .collect();
+                        if visible.is_empty() {
+                            continue;
                         }
-                        ui.label("Version Font Height");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_font_height))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                        any_visible = true;
+                        ui.separator();
+                        ui.heading(&capitalize_first(&cat.name));
+                        let mut groups: Vec<String> = vec![];
+                        for opt in &cat.options {
+                            if !groups.contains(&opt.category) {
+                                groups.push(opt.category.clone());
+                            }
                         }
-                        ui.label("Version Font Weight");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_font_weight))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                        for group in groups {
+                            let group_modified = cat.options.iter().any(|o| {
+                                o.category == group
+                                    && modified.contains(&(cat.name.clone(), o.key.clone()))
+                            });
+                            if !cat
+                                .options
+                                .iter()
+                                .any(|o| o.category == group && visible.contains(&o.key))
+                            {
+                                continue;
+                            }
+                            let mut reset_group = false;
+                            ui.horizontal(|ui| {
+                                let strong_color = ui.visuals().strong_text_color();
+                                ui.label(highlighted(ui, &group, &terms, strong_color));
+                                if group_modified
+                                    && ui.small_button("Reset all in category").clicked()
+                                {
+                                    reset_group = true;
+                                }
+                            });
+                            for opt in cat.options.iter_mut().filter(|o| o.category == group) {
+                                let default =
+                                    get_default_value(&state.peacock_defaults, &cat.name, &opt.key);
+                                let is_modified =
+                                    modified.contains(&(cat.name.clone(), opt.key.clone()));
+                                if reset_group && is_modified {
+                                    if let Some(default) = default {
+                                        opt.value = default.to_string();
+                                        log::info!(
+                                            "[+] Option {} reset to: {}",
+                                            opt.key,
+                                            opt.value
+                                        );
+                                    }
+                                    continue;
+                                }
+                                if !visible.contains(&opt.key) {
+                                    continue;
+                                }
+                                let title = highlighted(
+                                    ui,
+                                    &format_option_key(&opt.key),
+                                    &terms,
+                                    text_color,
+                                );
+                                if let Some(poss_vals) = &opt.possible_values {
+                                    ui.label(title);
+                                    let mut selected = opt.value.clone();
+                                    egui::ComboBox::from_id_salt(&opt.key)
+                                        .selected_text(&selected)
+                                        .show_ui(ui, |ui| {
+                                            for val in poss_vals {
+                                                ui.selectable_value(
+                                                    &mut selected,
+                                                    val.clone(),
+                                                    val.as_str(),
+                                                );
+                                            }
+                                        });
+                                    if selected != opt.value {
+                                        opt.value = selected;
+                                        log::info!(
+                                            "[+] Option {} changed to: {}",
+                                            opt.key,
+                                            opt.value
+                                        );
+                                    }
+                                } else if opt.value == "true" || opt.value == "false" {
+                                    let mut checked = opt.value == "true";
+                                    if ui.checkbox(&mut checked, title).changed() {
+                                        opt.value =
+                                            if checked { "true" } else { "false" }.to_string();
+                                        log::info!(
+                                            "[+] Option {} changed to: {}",
+                                            opt.key,
+                                            opt.value
+                                        );
+                                    }
+                                } else {
+                                    ui.label(title);
+                                    let response = ui.text_edit_singleline(&mut opt.value);
+                                    if response.changed() {
+                                        log::info!(
+                                            "[+] Option {} changed to: {}",
+                                            opt.key,
+                                            opt.value
+                                        );
+                                    }
+                                }
+                                if let (true, Some(default)) = (is_modified, default) {
+                                    ui.horizontal(|ui| {
+                                        ui.colored_label(
+                                            WARN_COLOR,
+                                            format!("Modified (default: {})", default),
+                                        );
+                                        if ui.small_button("Reset to default").clicked() {
+                                            opt.value = default.to_string();
+                                            log::info!(
+                                                "[+] Option {} reset to: {}",
+                                                opt.key,
+                                                opt.value
+                                            );
+                                        }
+                                    });
+                                }
+                                if pending.contains(&format!("{}.{}", cat.name, opt.key)) {
+                                    ui.colored_label(
+                                        WARN_COLOR,
+                                        "Pending restart",
+                                    );
+                                }
+                                ui.label(highlighted(ui, &opt.description, &terms, text_color));
+                            }
                         }
-                        ui.label("Version Text Width");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_text_width))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                    }
+                    if !any_visible {
+                        ui.separator();
+                        ui.label("No settings match");
+                    }
+                    ui.separator();
+                    ui.heading("Share Settings");
+                    if ui.button("Export bundle").clicked() {
+                        state.bundle_status = Some(
+                            match export_bundle(&state.spear_config, &state.peacock_categories) {
+                                Ok(path) => format!("Exported to {}", path.display()),
+                                Err(e) => e.to_string(),
+                            },
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Import from");
+                        ui.text_edit_singleline(&mut state.import_path);
+                        if ui.button("Preview").clicked() {
+                            match read_bundle(std::path::Path::new(state.import_path.trim())) {
+                                Ok(bundle) => {
+                                    state.import_preview = Some(preview_import(
+                                        &bundle,
+                                        &state.spear_config,
+                                        &state.peacock_categories,
+                                    ));
+                                    state.bundle_status = None;
+                                }
+                                Err(e) => {
+                                    state.import_preview = None;
+                                    state.bundle_status = Some(e.to_string());
+                                }
+                            }
                         }
-                        ui.label("Version Text Height");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_text_height))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                    });
+                    if let Some(preview) = state.import_preview.clone() {
+                        if preview.changes.is_empty() {
+                            ui.label("Importing this bundle changes nothing.");
                         }
-                        ui.label("Version Text Pos X");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_text_pos_x))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                        for change in &preview.changes {
+                            ui.label(format!("{}: {} -> {}", change.name, change.old, change.new));
                         }
-                        ui.label("Version Text Pos Y");
-                        if ui
-                            .add(egui::DragValue::new(&mut params.version_text_pos_y))
-                            .changed()
-                        {
-                            params.version_changed = true;
+                        for warning in &preview.warnings {
+                            ui.colored_label(WARN_COLOR, warning);
                         }
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(
+                                    !preview.changes.is_empty(),
+                                    egui::Button::new("Apply"),
+                                )
+                                .clicked()
+                            {
+                                match apply_import(&preview, &mut state.peacock_categories) {
+                                    Ok(_) => {
+                                        state.spear_config = load_spear_config();
+                                        state.spear_base = state.spear_config.clone();
+                                        state.spear_file.mark_synced();
+                                        state.loaded_profile = None;
+                                        track_peacock_source(state);
+                                        state.import_preview = None;
+                                        state.bundle_status = Some(format!(
+                                            "Imported {} setting(s)",
+                                            preview.changes.len()
+                                        ));
+                                    }
+                                    Err(e) => state.bundle_status = Some(e.to_string()),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                state.import_preview = None;
+                            }
+                        });
+                    }
+                    if let Some(status) = &state.bundle_status {
+                        ui.label(status);
+                    }
+
+                    ui.separator();
+                    ui.heading("Troubleshooting");
+                    let diagnostics_status = DIAGNOSTICS_STATUS.lock().unwrap().clone();
+                    let creating = diagnostics_status
+                        .as_deref()
+                        .is_some_and(|s| s.starts_with("Creating"));
+                    if ui
+                        .add_enabled(!creating, egui::Button::new("Create diagnostics bundle"))
+                        .on_hover_text(
+                            "Zips logs, settings and install details to attach to a bug report",
+                        )
+                        .clicked()
+                    {
+                        spawn_diagnostics_bundle();
                     }
-                    if state.dirty {
-                        log::info!("[+] Saving configs");
-                        save_spear_config(&state.spear_config);
-                        save_peacock_config(&state.peacock_categories);
-                        state.dirty = false;
+                    if let Some(status) = diagnostics_status {
+                        ui.label(status);
+                    }
+
+                    #[cfg(debug_assertions)]
+                    {
+                        ui.separator();
+                        ui.heading("Overlay Layout");
+                        crate::gui::debug::layout_editor(ui);
                     }
                 } else {
                     log::info!("[!] Settings state not initialized");
                 }
             });
         });
-    })
-    .unwrap();
+    });
+    if let Err(e) = result {
+        log::error!("[!] Settings window exited: {}", e);
+    }
+    *SETTINGS_HWND.lock().unwrap() = None;
+    *SETTINGS_CTX.lock().unwrap() = None;
+    *WINDOW.lock().unwrap() = SettingsWindow::Exited;
+    *EXTRA_SPAWNED.lock().unwrap() = false;
 }
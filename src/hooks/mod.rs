@@ -1,5 +1,6 @@
 #![allow(static_mut_refs)]
 
+use crate::error::{SpearError, SpearResult};
 use crate::{constants, core::resources};
 
 use std::ffi::CString;
@@ -42,13 +43,17 @@ unsafe extern "C" fn hook_sizeof_resource(hmodule: HMODULE, hrsrc: HRSRC) -> u32
 
 unsafe extern "C" fn hook_load_resource(hmodule: HMODULE, hrsrc: HRSRC) -> HGLOBAL {
     let original_hglobal = unsafe { HOOK_LOAD.as_ref().unwrap().call(hmodule, hrsrc) };
+    let (Some(lock), Some(sizeof)) = (unsafe { HOOK_LOCK.as_ref() }, unsafe { HOOK_SIZE.as_ref() })
+    else {
+        return original_hglobal;
+    };
     if !original_hglobal.is_null() {
-        let data_ptr = unsafe { HOOK_LOCK.as_ref().unwrap().call(original_hglobal) };
+        let data_ptr = unsafe { lock.call(original_hglobal) };
         if !data_ptr.is_null() {
-            let size = unsafe { HOOK_SIZE.as_ref().unwrap().call(hmodule, hrsrc) as usize };
+            let size = unsafe { sizeof.call(hmodule, hrsrc) as usize };
             if size > 0 {
                 let data = unsafe { std::slice::from_raw_parts(data_ptr as *const u8, size) };
-                if is_target_png(data) {
+                if is_target_png(data) && unsafe { !resources::FAKE_BG_RESOURCE.is_null() } {
                     log::info!("[+] Replacing target PNG resource with fake");
                     return unsafe { resources::FAKE_BG_RESOURCE as HGLOBAL };
                 }
@@ -66,75 +71,63 @@ unsafe extern "C" fn hook_lock_resource(hglobal: HGLOBAL) -> *mut winapi_c_void
     }
 }
 
-fn setup_load_resource_hook() {
+/// Detours `name` in kernel32 with `hook`. The detour is stored in `slot`
+/// before it is enabled, so the hook never runs without it.
+unsafe fn create_hook<T: retour::Function>(
+    name: &str,
+    hook: T,
+    slot: &mut Option<GenericDetour<T>>,
+) -> SpearResult<()> {
+    let context = format!("Failed to hook {}", name);
     unsafe {
-        let kernel32_handle = GetModuleHandleA(CString::new("kernel32.dll").unwrap().as_ptr());
-        if !kernel32_handle.is_null() {
-            let fn_ptr = GetProcAddress(
-                kernel32_handle,
-                CString::new("LoadResource").unwrap().as_ptr(),
-            );
-            if !fn_ptr.is_null() {
-                let target: FnLoadResource = std::mem::transmute(fn_ptr);
-                HOOK_LOAD =
-                    Some(GenericDetour::<FnLoadResource>::new(target, hook_load_resource).unwrap());
-                HOOK_LOAD.as_mut().unwrap().enable().unwrap();
-                log::info!("[+] Created LoadResource hook");
-            } else {
-                log::info!("[!] Failed to get LoadResource address");
-            }
+        let kernel32_handle = GetModuleHandleA(c"kernel32.dll".as_ptr());
+        if kernel32_handle.is_null() {
+            return Err(SpearError::hook(context, "kernel32.dll is not loaded"));
         }
-    }
-}
-
-fn setup_lock_resource_hook() {
-    unsafe {
-        let kernel32_handle = GetModuleHandleA(CString::new("kernel32.dll").unwrap().as_ptr());
-        if !kernel32_handle.is_null() {
-            let fn_ptr = GetProcAddress(
-                kernel32_handle,
-                CString::new("LockResource").unwrap().as_ptr(),
-            );
-            if !fn_ptr.is_null() {
-                let target: FnLockResource = std::mem::transmute(fn_ptr);
-                HOOK_LOCK =
-                    Some(GenericDetour::<FnLockResource>::new(target, hook_lock_resource).unwrap());
-                HOOK_LOCK.as_mut().unwrap().enable().unwrap();
-                log::info!("[+] Created LockResource hook");
-            } else {
-                log::info!("[!] Failed to get LockResource address");
-            }
+        let proc_name = CString::new(name).map_err(|e| SpearError::hook(&context, e))?;
+        let fn_ptr = GetProcAddress(kernel32_handle, proc_name.as_ptr());
+        if fn_ptr.is_null() {
+            return Err(SpearError::hook(context, "address not found"));
+        }
+        let target = T::from_ptr(fn_ptr as *const ());
+        let detour =
+            GenericDetour::<T>::new(target, hook).map_err(|e| SpearError::hook(&context, e))?;
+        let detour = slot.insert(detour);
+        if let Err(e) = detour.enable() {
+            *slot = None;
+            return Err(SpearError::hook(context, e));
         }
     }
+    log::info!("[+] Created {} hook", name);
+    Ok(())
 }
 
-fn setup_sizeof_resource_hook() {
-    unsafe {
-        let kernel32_handle = GetModuleHandleA(CString::new("kernel32.dll").unwrap().as_ptr());
-        if !kernel32_handle.is_null() {
-            let fn_ptr = GetProcAddress(
-                kernel32_handle,
-                CString::new("SizeofResource").unwrap().as_ptr(),
-            );
-            if !fn_ptr.is_null() {
-                let target: FnSizeofResource = std::mem::transmute(fn_ptr);
-                HOOK_SIZE = Some(
-                    GenericDetour::<FnSizeofResource>::new(target, hook_sizeof_resource).unwrap(),
-                );
-                HOOK_SIZE.as_mut().unwrap().enable().unwrap();
-                log::info!("[+] Created SizeofResource hook");
-            } else {
-                log::info!("[!] Failed to get SizeofResource address");
-            }
+/// Enables the resource hooks. LoadResource goes last because its hook calls
+/// into the other two; on failure none of them stays enabled.
+pub fn setup_hooks() -> SpearResult<()> {
+    let result = unsafe {
+        create_hook::<FnLockResource>("LockResource", hook_lock_resource, &mut HOOK_LOCK)
+            .and_then(|_| {
+                create_hook::<FnSizeofResource>(
+                    "SizeofResource",
+                    hook_sizeof_resource,
+                    &mut HOOK_SIZE,
+                )
+            })
+            .and_then(|_| {
+                create_hook::<FnLoadResource>("LoadResource", hook_load_resource, &mut HOOK_LOAD)
+            })
+    };
+    if let Err(e) = result {
+        unsafe {
+            HOOK_LOAD = None;
+            HOOK_SIZE = None;
+            HOOK_LOCK = None;
         }
+        return Err(e);
     }
-}
-
-pub fn setup_hooks() {
-    setup_load_resource_hook();
-    setup_lock_resource_hook();
-    setup_sizeof_resource_hook();
 
     log::info!("[+] All hooks enabled");
     log::info!("[+] Hooks are now active");
+    Ok(())
 }
@@ -7,6 +7,7 @@ use winapi::um::winnt::DLL_PROCESS_ATTACH;
 mod config;
 mod constants;
 mod core;
+mod error;
 mod exports;
 mod gui;
 mod hooks;
@@ -26,9 +27,6 @@ pub unsafe extern "system" fn DllMain(hinst: HINSTANCE, reason: DWORD, _reserved
 
         let _ = std::thread::spawn(|| {
             INIT.call_once(|| {
-                #[cfg(debug_assertions)]
-                crate::core::init::setup_logging();
-
                 let mut exe_path = [0u16; 260];
                 let len = GetModuleFileNameW(
                     std::ptr::null_mut(),
@@ -46,12 +44,26 @@ pub unsafe extern "system" fn DllMain(hinst: HINSTANCE, reason: DWORD, _reserved
                     "".to_string()
                 };
 
+                let host = std::path::Path::new(&exe_name)
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or("spear")
+                    .to_lowercase();
+                crate::core::init::setup_logging(&host);
+
                 log::info!("[+] Detected exe_name: {}", exe_name);
 
-                if exe_name == "Launcher.exe" {
-                    crate::core::init::initialize_everything();
+                // Failures are reported and leave the host running without spear.
+                let result = if exe_name == "Launcher.exe" {
+                    crate::core::init::initialize_everything()
                 } else if exe_name == "hitman3.exe" {
-                    crate::core::init::spawn_watchdog();
+                    crate::core::init::spawn_watchdog()
+                } else {
+                    Ok(())
+                };
+                if let Err(e) = result {
+                    crate::error::report(&e);
                 }
             });
         });
@@ -1,3 +1,4 @@
+use crate::error::{Context, SpearResult};
 use pelite::pe64::{Pe, PeFile};
 use std::fs;
 
@@ -8,7 +9,7 @@ pub fn rva_to_offset_manual(
 ) -> Option<usize> {
     for section in sections.iter() {
         if rva >= section.VirtualAddress as usize
-            && rva < (section.VirtualAddress + section.VirtualSize) as usize
+            && rva < section.VirtualAddress as usize + section.VirtualSize as usize
         {
             return Some(rva - section.VirtualAddress as usize + section.PointerToRawData as usize);
         }
@@ -16,6 +17,20 @@ pub fn rva_to_offset_manual(
     None
 }
 
+/// Resource directories are type, name and language; anything deeper is a
+/// malformed (or looping) table.
+const MAX_RESOURCE_DEPTH: usize = 3;
+
+fn read_u16(buffer: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buffer.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buffer.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
 #[allow(dead_code)]
 pub fn walk_dir_simple(
     sections: &pelite::pe64::headers::SectionHeaders,
@@ -24,77 +39,80 @@ pub fn walk_dir_simple(
     level: usize,
     base_rva: u32,
 ) {
-    if let Some(dir_offset) = rva_to_offset_manual(sections, dir_rva as usize) {
-        if dir_offset + 16 > buffer.len() {
-            return;
+    if level > MAX_RESOURCE_DEPTH {
+        return;
+    }
+    let Some(dir_offset) = rva_to_offset_manual(sections, dir_rva as usize) else {
+        return;
+    };
+    let (Some(num_named), Some(num_id)) = (
+        read_u16(buffer, dir_offset + 12),
+        read_u16(buffer, dir_offset + 14),
+    ) else {
+        return;
+    };
+    let num_entries = num_named as usize + num_id as usize;
+    let entries_start = dir_offset + 16;
+    for i in 0..num_entries {
+        let Some(offset_to) = read_u32(buffer, entries_start + i * 8 + 4) else {
+            break;
+        };
+        if offset_to & 0x80000000 != 0 {
+            let sub_dir_rva = base_rva.wrapping_add(offset_to & !0x80000000);
+            walk_dir_simple(sections, buffer, sub_dir_rva, level + 1, base_rva);
+            continue;
         }
-        let num_named =
-            u16::from_le_bytes(buffer[dir_offset + 12..dir_offset + 14].try_into().unwrap())
-                as usize;
-        let num_id =
-            u16::from_le_bytes(buffer[dir_offset + 14..dir_offset + 16].try_into().unwrap())
-                as usize;
-        let num_entries = num_named + num_id;
-        let entries_start = dir_offset + 16;
-        for i in 0..num_entries {
-            let entry_offset = entries_start + i * 8;
-            if entry_offset + 8 > buffer.len() {
-                continue;
-            }
-            let offset_to = u32::from_le_bytes(
-                buffer[entry_offset + 4..entry_offset + 8]
-                    .try_into()
-                    .unwrap(),
-            );
-            if offset_to & 0x80000000 != 0 {
-                let sub_dir_rva = base_rva + (offset_to & !0x80000000);
-                walk_dir_simple(sections, buffer, sub_dir_rva, level + 1, base_rva);
-            } else {
-                let data_entry_rva = base_rva + offset_to;
-                if let Some(data_entry_offset) =
-                    rva_to_offset_manual(sections, data_entry_rva as usize)
-                {
-                    if data_entry_offset + 16 <= buffer.len() {
-                        let res_rva_bytes = &buffer[data_entry_offset..data_entry_offset + 4];
-                        let size_bytes = &buffer[data_entry_offset + 4..data_entry_offset + 8];
-                        let res_rva = u32::from_le_bytes(res_rva_bytes.try_into().unwrap());
-                        let size = u32::from_le_bytes(size_bytes.try_into().unwrap()) as usize;
-                        if let Some(res_offset) = rva_to_offset_manual(sections, res_rva as usize) {
-                            if res_offset + size <= buffer.len() {
-                                let res_data = &buffer[res_offset..res_offset + size];
-                                crate::core::resources::analyze_data_cached(res_data);
-                            }
-                        }
-                    }
-                }
-            }
+        let data_entry_rva = base_rva.wrapping_add(offset_to);
+        let Some(data_entry_offset) = rva_to_offset_manual(sections, data_entry_rva as usize)
+        else {
+            continue;
+        };
+        let (Some(res_rva), Some(size)) = (
+            read_u32(buffer, data_entry_offset),
+            read_u32(buffer, data_entry_offset + 4),
+        ) else {
+            continue;
+        };
+        if let Some(res_data) = rva_to_offset_manual(sections, res_rva as usize)
+            .and_then(|res_offset| buffer.get(res_offset..res_offset.checked_add(size as usize)?))
+        {
+            crate::core::resources::analyze_data_cached(res_data);
         }
     }
 }
 
 #[allow(dead_code)]
-pub fn parse_pe_resources(exe_path: &std::path::Path) {
+pub fn parse_pe_resources(exe_path: &std::path::Path) -> SpearResult<()> {
     log::info!("[+] Parsing PE for resources...");
     log::info!("[+] Executable path: {:?}", exe_path);
-    let buffer = fs::read(&exe_path).unwrap();
+    let buffer = fs::read(exe_path).context(format!("Failed to read {}", exe_path.display()))?;
     log::info!("[+] Read {} bytes from executable", buffer.len());
-    if let Ok(pe) = PeFile::from_bytes(&buffer) {
-        log::info!("[+] PE parsed successfully");
-        let mut base_rva = 0;
-        for section in pe.section_headers().iter() {
-            let name = std::str::from_utf8(&section.Name)
-                .unwrap_or("")
-                .trim_end_matches('\0');
-            if name == ".rsrc" {
-                base_rva = section.VirtualAddress;
-                log::info!("[+] Found .rsrc section at RVA 0x{:x}", base_rva);
-                break;
-            }
-        }
-        log::info!("[+] Walking resource directory...");
-        walk_dir_simple(&pe.section_headers(), &buffer, base_rva, 0, base_rva);
-        log::info!("[+] Resource parsing complete");
-    } else {
-        log::info!("[!] Failed to parse PE");
+    let pe =
+        PeFile::from_bytes(&buffer).context(format!("Failed to parse {}", exe_path.display()))?;
+    log::info!("[+] PE parsed successfully");
+    let base_rva = pe
+        .section_headers()
+        .iter()
+        .find(|section| section.Name.starts_with(b".rsrc\0"))
+        .map(|section| section.VirtualAddress)
+        .context(format!("{} has no .rsrc section", exe_path.display()))?;
+    log::info!("[+] Found .rsrc section at RVA 0x{:x}", base_rva);
+    log::info!("[+] Walking resource directory...");
+    walk_dir_simple(pe.section_headers(), &buffer, base_rva, 0, base_rva);
+    log::info!("[+] Resource parsing complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_are_bounds_checked() {
+        let buffer = [0x01, 0x02, 0x03, 0x04, 0x05];
+        assert_eq!(read_u16(&buffer, 0), Some(0x0201));
+        assert_eq!(read_u32(&buffer, 1), Some(0x05040302));
+        assert_eq!(read_u32(&buffer, 2), None);
+        assert_eq!(read_u16(&buffer, usize::MAX), None);
     }
 }